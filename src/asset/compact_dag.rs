@@ -0,0 +1,75 @@
+use std::mem::size_of;
+use crate::asset::oct_dag::{OctDag, Node, Octant, OCTANT_COUNT, NULL_INDEX};
+
+/*
+the dag packed into a flat array of u32s, with only the octants that have something in them stored
+each node is a word with the occupancy of its octants in the lowest 8 bits, followed by the filled octants in order
+an octant is its index, colour, normal and extra, with the index being the word the child node starts at
+ */
+pub const OCTANT_WORDS: u32 = 4;
+
+pub struct CompactDag {
+	pub words: Vec<u32>,
+	pub max_depth: u32,
+}
+
+impl CompactDag {
+	//nodes keep the same order as in the dag, so the root is still at 0
+	pub fn from_dag(dag: &OctDag) -> Self {
+		let mut offsets = Vec::with_capacity(dag.nodes.len());
+		let mut word_count = 0;
+		for node in &dag.nodes {
+			offsets.push(word_count);
+			word_count += node.compact_words();
+		}
+
+		let mut words = Vec::with_capacity(word_count as usize);
+		for node in &dag.nodes {
+			words.push(node.child_mask());
+			for octant in node.octants {
+				if octant == Octant::new() {
+					continue;
+				}
+				words.push(if octant.index == NULL_INDEX { NULL_INDEX } else { offsets[octant.index as usize] });
+				words.extend([octant.colour, octant.normal, octant.extra]);
+			}
+		}
+		Self { words, max_depth: dag.max_depth }
+	}
+
+	//the octant as it would be in the original dag, with the index being a word offset instead
+	pub fn fetch_octant(&self, node_offset: u32, octant_index: usize) -> Octant {
+		let mask = self.words[node_offset as usize];
+		if mask & (1 << octant_index) == 0 {
+			return Octant::new();
+		}
+		let start = (node_offset + 1 + (mask & ((1 << octant_index) - 1)).count_ones() * OCTANT_WORDS) as usize;
+		Octant {
+			index: self.words[start],
+			colour: self.words[start + 1],
+			normal: self.words[start + 2],
+			extra: self.words[start + 3],
+		}
+	}
+
+	pub fn print_size(&self) {
+		println!("Compact word count: {}, Size in Mb: {}", self.words.len(), (self.words.len() * size_of::<u32>()) as f32 / 1000000.0)
+	}
+}
+
+impl Node {
+	//bit i is set when octant i is not empty
+	pub fn child_mask(&self) -> u32 {
+		let mut mask = 0;
+		for i in 0..OCTANT_COUNT {
+			if self.octants[i] != Octant::new() {
+				mask |= 1 << i;
+			}
+		}
+		mask
+	}
+	//size of the node once encoded in a compact dag
+	pub fn compact_words(&self) -> u32 {
+		1 + self.child_mask().count_ones() * OCTANT_WORDS
+	}
+}
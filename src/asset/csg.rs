@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use crate::asset::oct_dag::{OctDag, Node, Octant, DedupTable, OCTANT_COUNT, NULL_INDEX};
+
+/*
+boolean operations between two dags of the same depth, walking both at once and never expanding either
+where both dags have something, the octant of the first one is kept
+a pair of octants is only worked out once, so shared nodes in either dag stay cheap
+ */
+#[derive(Clone, Copy, PartialEq)]
+enum CsgOp {
+	Union,
+	Intersection,
+	Difference,
+}
+
+struct Csg<'a> {
+	op: CsgOp,
+	first: &'a OctDag,
+	second: &'a OctDag,
+	result: OctDag,
+	dedup: DedupTable,
+	combined: HashMap<(Octant, Octant), Octant>,
+}
+
+impl OctDag {
+	pub fn union(&self, other: &OctDag) -> OctDag {
+		Csg::run(CsgOp::Union, self, other)
+	}
+	pub fn intersection(&self, other: &OctDag) -> OctDag {
+		Csg::run(CsgOp::Intersection, self, other)
+	}
+	//everything in this dag that is not in the other
+	pub fn difference(&self, other: &OctDag) -> OctDag {
+		Csg::run(CsgOp::Difference, self, other)
+	}
+}
+
+impl<'a> Csg<'a> {
+	fn run(op: CsgOp, first: &'a OctDag, second: &'a OctDag) -> OctDag {
+		if first.max_depth != second.max_depth {
+			panic!("dags are not the same depth");
+		}
+		let mut csg = Csg {
+			op,
+			first,
+			second,
+			result: OctDag{nodes: vec![Node::new()], max_depth: first.max_depth},
+			dedup: DedupTable::new(first.max_depth),
+			combined: HashMap::new(),
+		};
+		for i in 0..OCTANT_COUNT {
+			csg.result.nodes[0].octants[i] = csg.combine(first.nodes[0].octants[i], second.nodes[0].octants[i], 1);
+		}
+		csg.result
+	}
+
+	fn combine(&mut self, a: Octant, b: Octant, depth: u32) -> Octant {
+		if let Some(octant) = self.resolve(a, b) {
+			return octant;
+		}
+		if let Some(octant) = self.combined.get(&(a, b)) {
+			return *octant;
+		}
+		let a_children = self.first.children(a);
+		let b_children = self.second.children(b);
+		let mut node = Node::new();
+		for i in 0..OCTANT_COUNT {
+			node.octants[i] = self.combine(a_children[i], b_children[i], depth + 1);
+		}
+		let octant = self.result.insert_node(&mut self.dedup, node, depth);
+		self.combined.insert((a, b), octant);
+		octant
+	}
+
+	//the result when it can be known without looking inside either octant
+	fn resolve(&self, a: Octant, b: Octant) -> Option<Octant> {
+		let (a_empty, b_empty) = (a == Octant::new(), b == Octant::new());
+		let (a_leaf, b_leaf) = (a.index == NULL_INDEX && !a_empty, b.index == NULL_INDEX && !b_empty);
+		match self.op {
+			CsgOp::Union => {
+				if a_leaf || (a_empty && b_empty) {
+					Some(a)
+				} else if a_empty && b_leaf {
+					Some(b)
+				} else {
+					None
+				}
+			},
+			CsgOp::Intersection => {
+				if a_empty || b_empty {
+					Some(Octant::new())
+				} else if a_leaf && b_leaf {
+					Some(a)
+				} else {
+					None
+				}
+			},
+			CsgOp::Difference => {
+				if a_empty || b_leaf {
+					Some(Octant::new())
+				} else if b_empty && a_leaf {
+					Some(a)
+				} else {
+					None
+				}
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use glam::{UVec3, Vec3};
+	use super::*;
+	use crate::asset::voxel_grid::VoxelGrid;
+
+	const DEPTH: u32 = 4;
+
+	fn leaf(colour: u32) -> Octant {
+		Octant { colour, normal: 1, ..Octant::new() }
+	}
+	fn grid(filled: impl Fn(UVec3) -> bool, colour: u32) -> VoxelGrid {
+		let mut grid = VoxelGrid::new(DEPTH);
+		for pos in positions() {
+			if filled(pos) {
+				grid.set(pos, leaf(colour));
+			}
+		}
+		grid
+	}
+	fn positions() -> impl Iterator<Item = UVec3> {
+		let size = 1 << DEPTH;
+		(0..size * size * size).map(move |i| UVec3::new(i % size, i / size % size, i / (size * size)))
+	}
+
+	//every voxel against the same operation done on each voxel alone, with the first dag's colour where both are filled
+	fn check(first: &VoxelGrid, second: &VoxelGrid) {
+		let (a, b) = (first.build(), second.build());
+		for op in [CsgOp::Union, CsgOp::Intersection, CsgOp::Difference] {
+			let reference = |a: bool, b: bool| match op {
+				CsgOp::Union => a || b,
+				CsgOp::Intersection => a && b,
+				CsgOp::Difference => a && !b,
+			};
+			let result = Csg::run(op, &a, &b);
+			assert!(result.validate().iter().all(|violation| !violation.is_fatal()));
+			for pos in positions() {
+				let (in_a, in_b) = (first.get(pos).is_some(), second.get(pos).is_some());
+				let voxel = result.voxel(pos);
+				assert_eq!(voxel != Octant::new(), reference(in_a, in_b), "at {}", pos);
+				if voxel != Octant::new() {
+					let expected = if in_a { first.get(pos) } else { second.get(pos) };
+					assert_eq!(voxel.colour, expected.unwrap().colour, "at {}", pos);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn overlapping_shapes() {
+		let cube = grid(|pos| pos.cmpge(UVec3::splat(2)).all() && pos.cmplt(UVec3::splat(10)).all(), 0xFF0000FF);
+		let ball = grid(|pos| (pos.as_vec3() - Vec3::splat(9.0)).length() < 5.0, 0x00FF00FF);
+		check(&cube, &ball);
+		check(&ball, &cube);
+	}
+
+	//the same small block in every 4 wide cell, so the same pairs of nodes come up again and again and are only worked out once
+	#[test]
+	fn shared_subtrees() {
+		let blocks = grid(|pos| (pos % 4).cmplt(UVec3::splat(2)).all(), 0xFF0000FF);
+		let slab = grid(|pos| pos.x < 6, 0x00FF00FF);
+		check(&blocks, &slab);
+		check(&slab, &blocks);
+
+		let (a, b) = (blocks.build(), slab.build());
+		//the cells stay shared in the result rather than each getting nodes of its own
+		assert!(a.intersection(&b).nodes.len() <= a.nodes.len());
+		assert!(a.union(&b).nodes.len() <= a.nodes.len() + b.nodes.len());
+	}
+}
@@ -0,0 +1,285 @@
+use std::fmt;
+use crate::{asset::{oct_dag::{OctDag, Node, Octant, OCTANT_COUNT, MASK_8BIT, pack_normal_u32, unpack_u32_f32}, validate::{DagViolation, MAX_DEPTH}}, io::{read_bin_file, write_bin_file}};
+
+/*
+file layout, all little endian:
+	magic 8 bytes, version u32, max depth u32, world size u32, node count u32, checksum u64
+	then node count nodes, each as 8 octants of index, colour, normal, extra u32s, with nothing after the last
+the checksum is fnv-1a over the node bytes only
+version 1 files stored normals as unsigned bytes, losing every negative component, they are still read with what is left of the normals re-encoded
+a dag that loads has been validated, only the violations that would crash or hang the shaders are errors
+ */
+const MAGIC: [u8; 8] = *b"VOXODAG\0";
+const VERSION: u32 = 2;
+const BYTE_NORMALS_VERSION: u32 = 1;
+const HEADER_SIZE: usize = 32;
+const OCTANT_SIZE: usize = 16;
+const NODE_SIZE: usize = OCTANT_SIZE * OCTANT_COUNT;
+
+#[derive(Debug)]
+pub enum DagFileError {
+	Io(std::io::Error),
+	BadMagic,
+	UnsupportedVersion(u32),
+	BadHeader(&'static str),
+	Truncated { expected: usize, found: usize },
+	TrailingBytes { expected: usize, found: usize }, //more than the node count in the header says there is, so the header or the file is wrong
+	ChecksumMismatch { expected: u64, found: u64 },
+	Invalid(Vec<DagViolation>),
+}
+
+impl OctDag {
+	pub fn save(&self, path: &str) -> Result<(), DagFileError> {
+		write_bin_file(path, &self.to_bytes())?;
+		Ok(())
+	}
+	pub fn load(path: &str) -> Result<Self, DagFileError> {
+		Self::from_bytes(&read_bin_file(path)?)
+	}
+
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut body = Vec::with_capacity(self.nodes.len() * NODE_SIZE);
+		for node in &self.nodes {
+			for octant in &node.octants {
+				for value in [octant.index, octant.colour, octant.normal, octant.extra] {
+					body.extend_from_slice(&value.to_le_bytes());
+				}
+			}
+		}
+
+		let mut bytes = Vec::with_capacity(HEADER_SIZE + body.len());
+		bytes.extend_from_slice(&MAGIC);
+		bytes.extend_from_slice(&VERSION.to_le_bytes());
+		bytes.extend_from_slice(&self.max_depth.to_le_bytes());
+		bytes.extend_from_slice(&self.world_size().to_le_bytes());
+		bytes.extend_from_slice(&(self.nodes.len() as u32).to_le_bytes());
+		bytes.extend_from_slice(&checksum(&body).to_le_bytes());
+		bytes.extend_from_slice(&body);
+		bytes
+	}
+
+	pub fn from_bytes(bytes: &[u8]) -> Result<Self, DagFileError> {
+		if bytes.len() < HEADER_SIZE {
+			if bytes.len() >= MAGIC.len() && bytes[..MAGIC.len()] != MAGIC {
+				return Err(DagFileError::BadMagic);
+			}
+			return Err(DagFileError::Truncated { expected: HEADER_SIZE, found: bytes.len() });
+		}
+		if bytes[..MAGIC.len()] != MAGIC {
+			return Err(DagFileError::BadMagic);
+		}
+		let version = read_u32(bytes, 8);
+		if version != VERSION && version != BYTE_NORMALS_VERSION {
+			return Err(DagFileError::UnsupportedVersion(version));
+		}
+		let max_depth = read_u32(bytes, 12);
+		let world_size = read_u32(bytes, 16);
+		let node_count = read_u32(bytes, 20) as usize;
+		let expected_checksum = u64::from_le_bytes(bytes[24..32].try_into().unwrap());
+
+		if max_depth == 0 || max_depth > MAX_DEPTH {
+			return Err(DagFileError::BadHeader("depth out of bounds"));
+		}
+		if world_size as u64 != 1 << (max_depth + 1) {
+			return Err(DagFileError::BadHeader("world size does not match depth"));
+		}
+		if node_count == 0 {
+			return Err(DagFileError::BadHeader("no root node"));
+		}
+
+		let body = &bytes[HEADER_SIZE..];
+		let expected_len = node_count * NODE_SIZE;
+		if body.len() < expected_len {
+			return Err(DagFileError::Truncated { expected: HEADER_SIZE + expected_len, found: bytes.len() });
+		}
+		if body.len() > expected_len {
+			return Err(DagFileError::TrailingBytes { expected: HEADER_SIZE + expected_len, found: bytes.len() });
+		}
+		let found_checksum = checksum(body);
+		if found_checksum != expected_checksum {
+			return Err(DagFileError::ChecksumMismatch { expected: expected_checksum, found: found_checksum });
+		}
+
+		let mut nodes = Vec::with_capacity(node_count);
+		for node_bytes in body.chunks_exact(NODE_SIZE) {
+			let mut node = Node::new();
+			for (i, octant_bytes) in node_bytes.chunks_exact(OCTANT_SIZE).enumerate() {
+				node.octants[i] = Octant {
+					index: read_u32(octant_bytes, 0),
+					colour: read_u32(octant_bytes, 4),
+					normal: if version == BYTE_NORMALS_VERSION { byte_normal(read_u32(octant_bytes, 8)) } else { read_u32(octant_bytes, 8) },
+					extra: read_u32(octant_bytes, 12),
+				};
+			}
+			nodes.push(node);
+		}
+		let dag = OctDag { nodes, max_depth };
+		let violations: Vec<DagViolation> = dag.validate().into_iter().filter(|violation| violation.is_fatal()).collect();
+		if !violations.is_empty() {
+			return Err(DagFileError::Invalid(violations));
+		}
+		Ok(dag)
+	}
+}
+
+impl fmt::Display for DagFileError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			DagFileError::Io(e) => write!(f, "dag file io error: {}", e),
+			DagFileError::BadMagic => write!(f, "not a dag file"),
+			DagFileError::UnsupportedVersion(version) => write!(f, "unsupported dag file version: {}", version),
+			DagFileError::BadHeader(reason) => write!(f, "bad dag file header: {}", reason),
+			DagFileError::Truncated { expected, found } => write!(f, "dag file truncated, expected {} bytes, found {}", expected, found),
+			DagFileError::TrailingBytes { expected, found } => write!(f, "dag file has bytes past the nodes, expected {} bytes, found {}", expected, found),
+			DagFileError::ChecksumMismatch { expected, found } => write!(f, "dag file checksum mismatch, expected {:#018x}, found {:#018x}", expected, found),
+			DagFileError::Invalid(violations) => write!(f, "dag file is not a valid dag, {} violations, the first: {}", violations.len(), violations[0]),
+		}
+	}
+}
+impl std::error::Error for DagFileError {}
+impl From<std::io::Error> for DagFileError {
+	fn from(e: std::io::Error) -> Self {
+		DagFileError::Io(e)
+	}
+}
+
+//an octant normal from a version 1 file, in the current encoding with the same density
+fn byte_normal(normal: u32) -> u32 {
+	pack_normal_u32(unpack_u32_f32(normal).truncate(), 0.0) | (normal & MASK_8BIT)
+}
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+	u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+//fnv-1a, 64 bit
+fn checksum(bytes: &[u8]) -> u64 {
+	let mut hash: u64 = 0xcbf29ce484222325;
+	for byte in bytes {
+		hash ^= *byte as u64;
+		hash = hash.wrapping_mul(0x100000001b3);
+	}
+	hash
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use glam::Vec3;
+	use crate::asset::oct_dag::{TestDagType, pack_f32_u32, unpack_u32_normal};
+
+	//a file as version 1 wrote it, each normal component as an unsigned byte with anything negative lost
+	fn version_1_bytes(dag: &OctDag) -> Vec<u8> {
+		let mut bytes = dag.to_bytes();
+		bytes[8..12].copy_from_slice(&BYTE_NORMALS_VERSION.to_le_bytes());
+		for octant_bytes in bytes[HEADER_SIZE..].chunks_exact_mut(OCTANT_SIZE) {
+			let normal = read_u32(octant_bytes, 8);
+			let direction = unpack_u32_normal(normal).truncate().max(Vec3::ZERO);
+			let byte_normal = pack_f32_u32(direction.extend(0.0)) | (normal & MASK_8BIT);
+			octant_bytes[8..12].copy_from_slice(&byte_normal.to_le_bytes());
+		}
+		rewrite_checksum(&mut bytes);
+		bytes
+	}
+
+	//for files changed after writing that should only fail for what was changed
+	fn rewrite_checksum(bytes: &mut [u8]) {
+		let body_checksum = checksum(&bytes[HEADER_SIZE..]);
+		bytes[24..32].copy_from_slice(&body_checksum.to_le_bytes());
+	}
+
+	#[test]
+	fn loads_version_1() {
+		let dag = OctDag::new_test(TestDagType::Pillar, 5);
+		let loaded = OctDag::from_bytes(&version_1_bytes(&dag)).unwrap();
+		assert_eq!(loaded.nodes.len(), dag.nodes.len());
+		for (node, loaded_node) in dag.nodes.iter().zip(&loaded.nodes) {
+			for (octant, loaded_octant) in node.octants.iter().zip(&loaded_node.octants) {
+				assert_eq!((octant.index, octant.colour, octant.extra), (loaded_octant.index, loaded_octant.colour, loaded_octant.extra));
+				assert_eq!(octant.normal & MASK_8BIT, loaded_octant.normal & MASK_8BIT);
+				//only normals with nothing negative came through version 1 whole
+				let (direction, loaded_direction) = (unpack_u32_normal(octant.normal).truncate(), unpack_u32_normal(loaded_octant.normal).truncate());
+				if *octant != Octant::new() && direction.min_element() >= 0.0 {
+					assert!(direction.angle_between(loaded_direction) < 0.01, "{} loaded as {}", direction, loaded_direction);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn round_trip() {
+		let dag = OctDag::new_test(TestDagType::Box, 5);
+		assert!(OctDag::from_bytes(&dag.to_bytes()).unwrap().nodes == dag.nodes);
+	}
+
+	#[test]
+	fn missing_file() {
+		assert!(matches!(OctDag::load("./no such dag file.dag"), Err(DagFileError::Io(_))));
+	}
+
+	#[test]
+	fn bad_magic() {
+		let mut bytes = OctDag::new_test(TestDagType::Box, 3).to_bytes();
+		bytes[0] = b'X';
+		assert!(matches!(OctDag::from_bytes(&bytes), Err(DagFileError::BadMagic)));
+		assert!(matches!(OctDag::from_bytes(&bytes[..12]), Err(DagFileError::BadMagic)));
+	}
+
+	#[test]
+	fn unsupported_version() {
+		let mut bytes = OctDag::new_test(TestDagType::Box, 3).to_bytes();
+		bytes[8..12].copy_from_slice(&(VERSION + 1).to_le_bytes());
+		assert!(matches!(OctDag::from_bytes(&bytes), Err(DagFileError::UnsupportedVersion(version)) if version == VERSION + 1));
+	}
+
+	#[test]
+	fn bad_header() {
+		let bytes = OctDag::new_test(TestDagType::Box, 3).to_bytes();
+		let with_u32 = |offset: usize, value: u32| {
+			let mut bytes = bytes.clone();
+			bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+			OctDag::from_bytes(&bytes)
+		};
+		assert!(matches!(with_u32(12, 0), Err(DagFileError::BadHeader("depth out of bounds"))));
+		assert!(matches!(with_u32(12, MAX_DEPTH + 1), Err(DagFileError::BadHeader("depth out of bounds"))));
+		assert!(matches!(with_u32(16, 1 << 3), Err(DagFileError::BadHeader("world size does not match depth"))));
+		assert!(matches!(with_u32(20, 0), Err(DagFileError::BadHeader("no root node"))));
+	}
+
+	#[test]
+	fn truncated() {
+		let bytes = OctDag::new_test(TestDagType::Box, 3).to_bytes();
+		assert!(matches!(OctDag::from_bytes(&bytes[..HEADER_SIZE - 1]),
+			Err(DagFileError::Truncated { expected: HEADER_SIZE, found }) if found == HEADER_SIZE - 1));
+		assert!(matches!(OctDag::from_bytes(&bytes[..bytes.len() - 1]),
+			Err(DagFileError::Truncated { expected, found }) if expected == bytes.len() && found == bytes.len() - 1));
+	}
+
+	#[test]
+	fn trailing_bytes() {
+		let mut bytes = OctDag::new_test(TestDagType::Box, 3).to_bytes();
+		let expected_len = bytes.len();
+		bytes.extend_from_slice(&[0; NODE_SIZE]);
+		assert!(matches!(OctDag::from_bytes(&bytes),
+			Err(DagFileError::TrailingBytes { expected, found }) if expected == expected_len && found == expected_len + NODE_SIZE));
+	}
+
+	#[test]
+	fn checksum_mismatch() {
+		let mut bytes = OctDag::new_test(TestDagType::Box, 3).to_bytes();
+		let last = bytes.len() - 1;
+		bytes[last] ^= 1;
+		assert!(matches!(OctDag::from_bytes(&bytes), Err(DagFileError::ChecksumMismatch { expected, found }) if expected != found));
+	}
+
+	#[test]
+	fn invalid() {
+		let dag = OctDag::new_test(TestDagType::Box, 3);
+		let mut bytes = dag.to_bytes();
+		//the index of the first octant of the root, pointing past the pool
+		bytes[HEADER_SIZE..HEADER_SIZE + 4].copy_from_slice(&(dag.nodes.len() as u32).to_le_bytes());
+		rewrite_checksum(&mut bytes);
+		let Err(DagFileError::Invalid(violations)) = OctDag::from_bytes(&bytes) else {
+			panic!("loaded a dag with an index past the pool");
+		};
+		assert_eq!(violations, vec![DagViolation::IndexOutOfBounds { node: 0, octant: 0, index: dag.nodes.len() as u32 }]);
+	}
+}
@@ -0,0 +1,187 @@
+use std::fmt;
+use glam::{UVec3, IVec3, Vec3, Vec4};
+use crate::{asset::{oct_dag::{OctDag, Octant, pack_f32_u32, pack_normal_u32, MASK_8BIT}, voxel_grid::VoxelGrid}, io::read_bin_file};
+
+/*
+dense scalar volumes such as ct or mri scans, stored raw as x fastest then y then z, little endian
+integer samples are normalized to [0, 1], floats are kept as they are
+ */
+#[derive(Clone, Copy, Debug)]
+pub enum ScalarType {
+	U8,
+	U16,
+	F32,
+}
+pub struct DensityVolume {
+	pub dims: UVec3,
+	pub values: Vec<f32>,
+}
+
+#[derive(Debug)]
+pub enum DensityError {
+	Io(std::io::Error),
+	SizeMismatch { expected: usize, found: usize },
+	TooLarge(u32),
+}
+
+impl DensityVolume {
+	pub fn load_raw(path: &str, dims: UVec3, scalar_type: ScalarType) -> Result<Self, DensityError> {
+		Self::from_raw_bytes(&read_bin_file(path)?, dims, scalar_type)
+	}
+	pub fn from_raw_bytes(bytes: &[u8], dims: UVec3, scalar_type: ScalarType) -> Result<Self, DensityError> {
+		let count = dims.x as usize * dims.y as usize * dims.z as usize;
+		let expected = count * scalar_type.size();
+		if bytes.len() != expected {
+			return Err(DensityError::SizeMismatch { expected, found: bytes.len() });
+		}
+		let values = match scalar_type {
+			ScalarType::U8 => bytes.iter().map(|value| *value as f32 / u8::MAX as f32).collect(),
+			ScalarType::U16 => bytes.chunks_exact(2)
+				.map(|value| u16::from_le_bytes([value[0], value[1]]) as f32 / u16::MAX as f32)
+				.collect(),
+			ScalarType::F32 => bytes.chunks_exact(4)
+				.map(|value| f32::from_le_bytes(value.try_into().unwrap()))
+				.collect(),
+		};
+		Ok(Self { dims, values })
+	}
+
+	//samples outside the volume are taken from the nearest edge
+	pub fn sample(&self, pos: IVec3) -> f32 {
+		let pos = pos.clamp(IVec3::ZERO, self.dims.as_ivec3() - IVec3::ONE).as_uvec3();
+		let (x, y, z) = (pos.x as usize, pos.y as usize, pos.z as usize);
+		self.values[x + self.dims.x as usize * (y + self.dims.y as usize * z)]
+	}
+	//points from higher values to lower, so out of the denser material
+	pub fn gradient_normal(&self, pos: IVec3) -> Vec3 {
+		-Vec3::new(
+			self.sample(pos + IVec3::X) - self.sample(pos - IVec3::X),
+			self.sample(pos + IVec3::Y) - self.sample(pos - IVec3::Y),
+			self.sample(pos + IVec3::Z) - self.sample(pos - IVec3::Z)).normalize_or_zero()
+	}
+
+	pub fn to_dag<F>(&self, transfer: F) -> Result<OctDag, DensityError>
+	where F: Fn(f32) -> (Vec4, f32) {
+		Ok(self.to_grid(transfer)?.build())
+	}
+
+	/*
+	the transfer function maps a sample to its rgba colour and density
+	anything that ends up with no density is left out, so empty space never makes it into the dag
+	 */
+	pub fn to_grid<F>(&self, transfer: F) -> Result<VoxelGrid, DensityError>
+	where F: Fn(f32) -> (Vec4, f32) {
+		let extent = self.dims.max_element();
+		let depth = VoxelGrid::depth_for(extent);
+		if depth > 16 {
+			return Err(DensityError::TooLarge(extent));
+		}
+		let mut grid = VoxelGrid::new(depth);
+		for z in 0..self.dims.z {
+			for y in 0..self.dims.y {
+				for x in 0..self.dims.x {
+					let pos = UVec3::new(x, y, z);
+					let (colour, density) = transfer(self.sample(pos.as_ivec3()));
+					let normal = self.gradient_normal(pos.as_ivec3());
+					let mut octant = Octant::new();
+					octant.normal = pack_normal_u32(normal, density);
+					if octant.normal & MASK_8BIT == 0 {
+						continue;
+					}
+					octant.colour = pack_f32_u32(colour);
+					grid.set(pos, octant);
+				}
+			}
+		}
+		Ok(grid)
+	}
+}
+
+impl ScalarType {
+	pub fn size(&self) -> usize {
+		match self {
+			ScalarType::U8 => 1,
+			ScalarType::U16 => 2,
+			ScalarType::F32 => 4,
+		}
+	}
+}
+
+impl fmt::Display for DensityError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			DensityError::Io(e) => write!(f, "density volume io error: {}", e),
+			DensityError::SizeMismatch { expected, found } => write!(f, "density volume size mismatch, expected {} bytes, found {}", expected, found),
+			DensityError::TooLarge(extent) => write!(f, "density volume too large for a dag, extent: {}", extent),
+		}
+	}
+}
+impl std::error::Error for DensityError {}
+impl From<std::io::Error> for DensityError {
+	fn from(e: std::io::Error) -> Self {
+		DensityError::Io(e)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::asset::oct_dag::unpack_u32_normal;
+
+	const DIMS: UVec3 = UVec3::new(3, 2, 2);
+
+	//rising along x from nothing to full, the same in every row
+	fn raw_bytes(scalar_type: ScalarType) -> Vec<u8> {
+		let mut bytes = Vec::new();
+		for i in 0..DIMS.x * DIMS.y * DIMS.z {
+			let x = (i % DIMS.x) as usize;
+			match scalar_type {
+				ScalarType::U8 => bytes.push([0u8, 128, 255][x]),
+				ScalarType::U16 => bytes.extend_from_slice(&[0u16, 32768, 65535][x].to_le_bytes()),
+				ScalarType::F32 => bytes.extend_from_slice(&[0.0f32, 0.5, 1.0][x].to_le_bytes()),
+			}
+		}
+		bytes
+	}
+	//red as bright as the sample, with anything under a quarter left out
+	fn transfer(value: f32) -> (Vec4, f32) {
+		if value < 0.25 { (Vec4::ZERO, 0.0) } else { (Vec4::new(value, 0.0, 0.0, 1.0), value) }
+	}
+
+	#[test]
+	fn import_through_transfer() {
+		for scalar_type in [ScalarType::U8, ScalarType::U16, ScalarType::F32] {
+			let volume = DensityVolume::from_raw_bytes(&raw_bytes(scalar_type), DIMS, scalar_type).unwrap();
+			assert!((volume.sample(IVec3::new(1, 1, 1)) - 0.5).abs() < 0.01);
+			assert_eq!(volume.sample(IVec3::new(-4, 0, 9)), volume.sample(IVec3::new(0, 0, 1)));
+
+			let grid = volume.to_grid(transfer).unwrap();
+			let dag = volume.to_dag(transfer).unwrap();
+			assert_eq!(grid.max_depth, 2);
+			assert_eq!(grid.voxels.len(), 8);
+			for x in 0..DIMS.x {
+				for (y, z) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+					let pos = UVec3::new(x, y, z);
+					let Some(octant) = grid.get(pos) else {
+						assert_eq!(x, 0);
+						assert!(dag.voxel(pos) == Octant::new());
+						continue;
+					};
+					let value = volume.sample(pos.as_ivec3());
+					assert_eq!(octant.colour, pack_f32_u32(Vec4::new(value, 0.0, 0.0, 1.0)));
+					let normal = unpack_u32_normal(octant.normal);
+					assert!((normal.w - value).abs() < 0.01, "density {} for {}", normal.w, value);
+					assert!(normal.truncate().angle_between(-Vec3::X) < 0.01, "normal {}", normal);
+					assert!(dag.voxel(pos) == *octant);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn size_mismatch() {
+		let bytes = raw_bytes(ScalarType::U16);
+		assert!(matches!(DensityVolume::from_raw_bytes(&bytes[1..], DIMS, ScalarType::U16),
+			Err(DensityError::SizeMismatch { expected: 24, found: 23 })));
+	}
+}
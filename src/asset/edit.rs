@@ -0,0 +1,123 @@
+use glam::{UVec3, Vec3};
+use crate::asset::oct_dag::{OctDag, Node, Octant, DedupTable, OCTANT_COUNT, NULL_INDEX};
+
+/*
+editing a built dag, with positions being voxels in [0, 2^max_depth) along each axis, the same as a voxel grid
+nodes can be shared, so they are never changed in place, instead the nodes along the edited paths are copied
+the copies go through the dedup table, and the octants above them get their aggregates redone the same as fill_oct
+the nodes that were replaced are left in the pool until it is compacted
+a table for a dag that was not just built can be made with DedupTable::from_dag
+ */
+#[derive(Clone, Copy, PartialEq)]
+enum Coverage {
+	Inside,
+	Outside,
+	Partial,
+}
+
+impl OctDag {
+	//the index of the octant is ignored, the voxel is always a leaf
+	pub fn set_voxel(&mut self, dedup: &mut DedupTable, pos: UVec3, octant: Octant) {
+		self.fill_box(dedup, pos, pos + UVec3::ONE, octant);
+	}
+	pub fn clear_voxel(&mut self, dedup: &mut DedupTable, pos: UVec3) {
+		self.fill_box(dedup, pos, pos + UVec3::ONE, Octant::new());
+	}
+
+	//fills every voxel from min up to but not including max
+	pub fn fill_box(&mut self, dedup: &mut DedupTable, min: UVec3, max: UVec3, octant: Octant) {
+		self.edit(dedup, octant, &|low: UVec3, size: u32| {
+			let high = low + UVec3::splat(size);
+			if high.cmple(min).any() || low.cmpge(max).any() {
+				Coverage::Outside
+			} else if low.cmpge(min).all() && high.cmple(max).all() {
+				Coverage::Inside
+			} else {
+				Coverage::Partial
+			}
+		});
+	}
+
+	//empties every voxel whose center is within the radius, both being in voxels
+	pub fn carve_sphere(&mut self, dedup: &mut DedupTable, center: Vec3, radius: f32) {
+		self.edit(dedup, Octant::new(), &|low: UVec3, size: u32| {
+			//the centers of the voxels in the cube, rather than its corners
+			let first = low.as_vec3() + Vec3::splat(0.5);
+			let last = first + Vec3::splat(size as f32 - 1.0);
+			let nearest = center.clamp(first, last).distance(center);
+			let farthest = (center - first).abs().max((center - last).abs()).length();
+			if nearest > radius {
+				Coverage::Outside
+			} else if farthest <= radius {
+				Coverage::Inside
+			} else {
+				Coverage::Partial
+			}
+		});
+	}
+
+	//sets everything the coverage says is inside to the octant, the coverage being given the lowest voxel and width of a cube
+	fn edit<F>(&mut self, dedup: &mut DedupTable, octant: Octant, coverage: &F)
+	where F: Fn(UVec3, u32) -> Coverage {
+		let mut fill = octant;
+		fill.index = NULL_INDEX;
+		let size = 1 << self.max_depth;
+		if coverage(UVec3::ZERO, size) == Coverage::Outside {
+			return;
+		}
+		//the root is never shared, so it can be changed in place
+		for i in 0..OCTANT_COUNT {
+			let low = octant_offset(i) * (size / 2);
+			self.nodes[0].octants[i] = self.edit_octant(dedup, self.nodes[0].octants[i], fill, low, size / 2, coverage);
+		}
+	}
+
+	fn edit_octant<F>(&mut self, dedup: &mut DedupTable, octant: Octant, fill: Octant, low: UVec3, size: u32, coverage: &F) -> Octant
+	where F: Fn(UVec3, u32) -> Coverage {
+		match coverage(low, size) {
+			Coverage::Outside => octant,
+			Coverage::Inside => fill,
+			Coverage::Partial => {
+				let mut node = Node{octants: self.children(octant)};
+				for i in 0..OCTANT_COUNT {
+					let child_low = low + octant_offset(i) * (size / 2);
+					node.octants[i] = self.edit_octant(dedup, node.octants[i], fill, child_low, size / 2, coverage);
+				}
+				let depth = self.max_depth - size.trailing_zeros();
+				self.insert_node(dedup, node, depth)
+			},
+		}
+	}
+}
+
+fn octant_offset(i: usize) -> UVec3 {
+	UVec3::new(i as u32 & 1, (i as u32 >> 1) & 1, (i as u32 >> 2) & 1)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::asset::voxel_grid::VoxelGrid;
+
+	#[test]
+	fn edit_to_only_normal_is_kept() {
+		let mut leaf = Octant::new();
+		leaf.colour = 0xFFFFFFFF;
+		leaf.normal = 0x80080001;
+		let mut grid = VoxelGrid::new(4);
+		grid.set(UVec3::new(0, 0, 0), leaf);
+		grid.set(UVec3::new(8, 0, 0), leaf);
+		let mut dag = grid.build();
+		let mut dedup = DedupTable::from_dag(&dag);
+
+		let mut edited = leaf;
+		edited.normal = 0x7FF80001;
+		edited.extra = 3;
+		dag.set_voxel(&mut dedup, UVec3::new(0, 0, 0), edited);
+		assert_eq!(dag.voxel(UVec3::new(0, 0, 0)), edited);
+		assert_eq!(dag.voxel(UVec3::new(8, 0, 0)), leaf);
+
+		dag.set_voxel(&mut dedup, UVec3::new(0, 0, 0), leaf);
+		assert_eq!(dag.voxel(UVec3::new(0, 0, 0)), leaf);
+	}
+}
@@ -0,0 +1,154 @@
+use crate::asset::oct_dag::{OctDag, Node, OCTANT_COUNT, NULL_INDEX};
+
+/*
+removing the nodes the root can no longer reach, which editing and merging leave behind
+the map returned has the new index of every old node, or NULL_INDEX for those dropped, so copies elsewhere can be patched
+any dedup table for the dag points at the old indices, and has to be made again with DedupTable::from_dag
+ */
+#[derive(Clone, Copy, PartialEq)]
+pub enum NodeOrder {
+	Kept, //the same order as before, so the fewest nodes move
+	DepthFirst, //each node followed by the first of its children, so a ray going down tends to stay close
+	BreadthFirst, //one level after another
+}
+
+impl OctDag {
+	//the root is always kept at 0
+	pub fn collect_garbage(&mut self, order: NodeOrder) -> Vec<u32> {
+		let kept: Vec<u32> = match order {
+			NodeOrder::Kept => {
+				let reachable = self.reachable();
+				(0..self.nodes.len() as u32).filter(|index| reachable[*index as usize]).collect()
+			},
+			NodeOrder::DepthFirst => self.depth_first(),
+			NodeOrder::BreadthFirst => self.levels().into_iter().flatten().collect(),
+		};
+		self.compact(&kept)
+	}
+
+	pub fn remap_children(&mut self, index: u32, remap: &[u32]) {
+		for octant in self.nodes[index as usize].octants.iter_mut() {
+			if octant.index != NULL_INDEX {
+				octant.index = remap[octant.index as usize];
+			}
+		}
+	}
+
+	fn reachable(&self) -> Vec<bool> {
+		let mut reachable = vec![false; self.nodes.len()];
+		let mut stack = vec![0];
+		reachable[0] = true;
+		while let Some(index) = stack.pop() {
+			for octant in self.nodes[index as usize].octants {
+				if octant.index != NULL_INDEX && !reachable[octant.index as usize] {
+					reachable[octant.index as usize] = true;
+					stack.push(octant.index);
+				}
+			}
+		}
+		reachable
+	}
+
+	//the reachable nodes in the order they are first found going down octant 0 first
+	fn depth_first(&self) -> Vec<u32> {
+		let mut visited = vec![false; self.nodes.len()];
+		let mut order = Vec::new();
+		let mut stack = vec![0];
+		while let Some(index) = stack.pop() {
+			if visited[index as usize] {
+				continue;
+			}
+			visited[index as usize] = true;
+			order.push(index);
+			for i in (0..OCTANT_COUNT).rev() {
+				let child = self.nodes[index as usize].octants[i].index;
+				if child != NULL_INDEX && !visited[child as usize] {
+					stack.push(child);
+				}
+			}
+		}
+		order
+	}
+
+	//only the listed nodes, in the order listed, with their children pointed at where they end up
+	fn compact(&mut self, kept: &[u32]) -> Vec<u32> {
+		let mut remap = vec![NULL_INDEX; self.nodes.len()];
+		let mut nodes: Vec<Node> = Vec::with_capacity(kept.len());
+		for index in kept {
+			remap[*index as usize] = nodes.len() as u32;
+			nodes.push(self.nodes[*index as usize]);
+		}
+		self.nodes = nodes;
+		for index in 0..self.nodes.len() {
+			self.remap_children(index as u32, &remap);
+		}
+		remap
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use glam::{UVec3, Vec3};
+	use super::*;
+	use crate::asset::{oct_dag::{Octant, DedupTable}, voxel_grid::VoxelGrid};
+
+	const DEPTH: u32 = 4;
+
+	//a ball with a hole carved in it, the nodes the carving replaced left in the pool along with one nothing ever pointed to
+	fn edited() -> OctDag {
+		let mut grid = VoxelGrid::new(DEPTH);
+		for x in 0..16 {
+			for y in 0..16 {
+				for z in 0..16 {
+					let pos = UVec3::new(x, y, z);
+					if (pos.as_vec3() - Vec3::splat(7.5)).length() < 7.0 {
+						grid.set(pos, Octant { colour: x << 24 | y << 16 | z << 8 | 0xFF, normal: 1, ..Octant::new() });
+					}
+				}
+			}
+		}
+		let mut dag = grid.build();
+		let mut dedup = DedupTable::from_dag(&dag);
+		dag.carve_sphere(&mut dedup, Vec3::new(8.0, 12.0, 4.0), 3.0);
+		dag.nodes.push(dag.nodes[1]);
+		dag
+	}
+	fn voxels(dag: &OctDag) -> Vec<Octant> {
+		(0..16 * 16 * 16).map(|i| dag.voxel(UVec3::new(i % 16, i / 16 % 16, i / 256))).collect()
+	}
+
+	#[test]
+	fn collects_unreachable() {
+		for order in [NodeOrder::Kept, NodeOrder::DepthFirst, NodeOrder::BreadthFirst] {
+			let old = edited();
+			let reachable = old.reachable();
+			assert!(reachable.iter().any(|reachable| !reachable), "nothing to collect");
+
+			let mut dag = edited();
+			let remap = dag.collect_garbage(order);
+			assert_eq!(voxels(&dag), voxels(&old));
+			assert_eq!(dag.nodes.len(), reachable.iter().filter(|reachable| **reachable).count());
+			assert!(dag.reachable().iter().all(|reachable| *reachable));
+			assert_eq!(remap[0], 0);
+			for (index, new_index) in remap.iter().enumerate() {
+				if !reachable[index] {
+					assert_eq!(*new_index, NULL_INDEX);
+					continue;
+				}
+				let (old_node, new_node) = (old.nodes[index], dag.nodes[*new_index as usize]);
+				for (old_octant, new_octant) in old_node.octants.iter().zip(new_node.octants) {
+					let expected = if old_octant.index == NULL_INDEX { NULL_INDEX } else { remap[old_octant.index as usize] };
+					assert_eq!(Octant { index: expected, ..*old_octant }, new_octant);
+				}
+			}
+
+			//each order laid out as it says, in the new dag
+			let all: Vec<u32> = (0..dag.nodes.len() as u32).collect();
+			match order {
+				NodeOrder::Kept => assert!(remap.iter().filter(|index| **index != NULL_INDEX).is_sorted()),
+				NodeOrder::DepthFirst => assert_eq!(dag.depth_first(), all),
+				NodeOrder::BreadthFirst => assert_eq!(dag.levels().into_iter().flatten().collect::<Vec<_>>(), all),
+			}
+		}
+	}
+}
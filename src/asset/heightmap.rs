@@ -0,0 +1,414 @@
+use std::fmt;
+use glam::{UVec3, Vec3, Vec4};
+use crate::{asset::{oct_dag::{OctDag, Node, Octant, DedupTable, OCTANT_COUNT, pack_f32_u32, pack_u8_u32, pack_normal_u32}, voxel_grid::VoxelGrid}, io::read_bin_file};
+
+/*
+terrain from gis style heightmaps, 8 or 16 bit grayscale pgm or png, with an optional colour map of the same size
+the image is laid flat with x along the rows and z down the columns, y being up, starting from the corner of the dag at 0
+heights are normalized to [0, 1], and the vertical scale is how many voxels high a height of 1 is
+every column is solid from the bottom up to its height, but only voxels that can be seen are built as single leaves
+anything with a column at least as high all around it is a leaf as big as the octant it fills
+ */
+pub struct Heightmap {
+	pub width: u32,
+	pub depth: u32,
+	pub heights: Vec<f32>, //x fastest
+}
+pub struct ColourMap {
+	pub width: u32,
+	pub depth: u32,
+	pub colours: Vec<u32>, //rgba packed the same as octant colours
+}
+
+#[derive(Debug)]
+pub enum HeightmapError {
+	Io(std::io::Error),
+	Png(png::DecodingError),
+	UnsupportedFormat(&'static str),
+	BadHeader(&'static str),
+	Truncated,
+	SizeMismatch { heightmap: (u32, u32), colour_map: (u32, u32) },
+	TooLarge(u32),
+}
+
+//every sample of an image scaled to 16 bits, with as many channels as it has
+struct Raster {
+	width: u32,
+	height: u32,
+	channels: usize,
+	samples: Vec<u16>,
+}
+
+const PNG_MAGIC: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+const MAX_DEPTH: u32 = 16;
+//with no colour map the ground goes from this at the bottom to white at the top
+const LOW_COLOUR: Vec4 = Vec4::new(0.4, 0.35, 0.3, 1.0);
+
+impl Heightmap {
+	pub fn load(path: &str) -> Result<Self, HeightmapError> {
+		Self::from_bytes(&read_bin_file(path)?)
+	}
+	//colour images have their first channel used
+	pub fn from_bytes(bytes: &[u8]) -> Result<Self, HeightmapError> {
+		let raster = Raster::decode(bytes)?;
+		Ok(Self {
+			width: raster.width,
+			depth: raster.height,
+			heights: raster.samples.chunks_exact(raster.channels).map(|pixel| pixel[0] as f32 / u16::MAX as f32).collect(),
+		})
+	}
+	pub fn height(&self, x: u32, z: u32) -> f32 {
+		self.heights[(x + z * self.width) as usize]
+	}
+}
+
+impl ColourMap {
+	pub fn load(path: &str) -> Result<Self, HeightmapError> {
+		Self::from_bytes(&read_bin_file(path)?)
+	}
+	//grayscale images are taken as gray, and anything without alpha is opaque
+	pub fn from_bytes(bytes: &[u8]) -> Result<Self, HeightmapError> {
+		let raster = Raster::decode(bytes)?;
+		let to_u8 = |sample: u16| (sample >> 8) as u8;
+		let colours = raster.samples.chunks_exact(raster.channels).map(|pixel| {
+			match pixel {
+				[gray] => pack_u8_u32((to_u8(*gray), to_u8(*gray), to_u8(*gray), u8::MAX)),
+				[gray, alpha] => pack_u8_u32((to_u8(*gray), to_u8(*gray), to_u8(*gray), to_u8(*alpha))),
+				[r, g, b] => pack_u8_u32((to_u8(*r), to_u8(*g), to_u8(*b), u8::MAX)),
+				[r, g, b, a, ..] => pack_u8_u32((to_u8(*r), to_u8(*g), to_u8(*b), to_u8(*a))),
+				[] => 0,
+			}
+		}).collect();
+		Ok(Self { width: raster.width, depth: raster.height, colours })
+	}
+}
+
+impl OctDag {
+	pub fn from_heightmap(heightmap: &Heightmap, colour_map: Option<&ColourMap>, vertical_scale: f32) -> Result<Self, HeightmapError> {
+		if let Some(colour_map) = colour_map {
+			if (colour_map.width, colour_map.depth) != (heightmap.width, heightmap.depth) {
+				return Err(HeightmapError::SizeMismatch { heightmap: (heightmap.width, heightmap.depth), colour_map: (colour_map.width, colour_map.depth) });
+			}
+		}
+		let extent = heightmap.width.max(heightmap.depth).max(vertical_scale.max(0.0).ceil() as u32 + 1);
+		let max_depth = VoxelGrid::depth_for(extent);
+		if max_depth > MAX_DEPTH {
+			return Err(HeightmapError::TooLarge(extent));
+		}
+		let columns = Columns::new(heightmap, colour_map, vertical_scale, max_depth);
+
+		let mut dag = OctDag{nodes: vec![Node::new()], max_depth};
+		let mut dedup = DedupTable::new(max_depth);
+		let half = 1 << (max_depth - 1);
+		for i in 0..OCTANT_COUNT {
+			dag.nodes[0].octants[i] = columns.fill(&mut dag, &mut dedup, octant_corner(i) * half, 1);
+		}
+		Ok(dag)
+	}
+}
+
+/*
+the top voxel of each column, -1 for none
+with the lowest and highest of them over each aligned square of every power of two size, for knowing when an octant is all above or below the ground
+only the squares over the heightmap are kept, as the dag can be far bigger when the vertical scale is large, and any past it have no columns
+ */
+struct Columns<'a> {
+	heightmap: &'a Heightmap,
+	colour_map: Option<&'a ColourMap>,
+	vertical_scale: f32,
+	max_depth: u32,
+	levels: Vec<ColumnLevel>,
+}
+//squares of one size, x fastest
+struct ColumnLevel {
+	width: usize,
+	depth: usize,
+	lowest: Vec<i32>,
+	highest: Vec<i32>,
+}
+
+impl<'a> Columns<'a> {
+	fn new(heightmap: &'a Heightmap, colour_map: Option<&'a ColourMap>, vertical_scale: f32, max_depth: u32) -> Self {
+		let tops: Vec<i32> = heightmap.heights.iter().map(|height| (height * vertical_scale).floor() as i32).collect();
+		let mut levels = vec![ColumnLevel { width: heightmap.width as usize, depth: heightmap.depth as usize, lowest: tops.clone(), highest: tops }];
+		for _ in 1..=max_depth {
+			let below = levels.last().unwrap();
+			let (width, depth) = (below.width.div_ceil(2), below.depth.div_ceil(2));
+			let reduce = |previous: &[i32], pick: fn(i32, i32) -> i32| -> Vec<i32> {
+				(0..width * depth).map(|i| {
+					let (x, z) = (i % width * 2, i / width * 2);
+					let get = |x: usize, z: usize| if x < below.width && z < below.depth { previous[x + z * below.width] } else { -1 };
+					pick(pick(get(x, z), get(x + 1, z)), pick(get(x, z + 1), get(x + 1, z + 1)))
+				}).collect()
+			};
+			let (lowest, highest) = (reduce(&below.lowest, i32::min), reduce(&below.highest, i32::max));
+			levels.push(ColumnLevel { width, depth, lowest, highest });
+		}
+		Self { heightmap, colour_map, vertical_scale, max_depth, levels }
+	}
+	//the lowest and highest top over the square, -1 for squares off the heightmap
+	fn range(&self, level: usize, x: i64, z: i64) -> (i32, i32) {
+		let level = &self.levels[level];
+		if x < 0 || z < 0 || x >= level.width as i64 || z >= level.depth as i64 {
+			return (-1, -1);
+		}
+		let i = x as usize + z as usize * level.width;
+		(level.lowest[i], level.highest[i])
+	}
+
+	fn fill(&self, dag: &mut OctDag, dedup: &mut DedupTable, corner: UVec3, depth: u32) -> Octant {
+		let level = (self.max_depth - depth) as usize;
+		let size = 1 << level;
+		let (cell_x, cell_z) = ((corner.x >> level) as i64, (corner.z >> level) as i64);
+		if corner.y as i32 > self.range(level, cell_x, cell_z).1 {
+			return Octant::new();
+		}
+		//the square and those around it, so that voxels on the side of a cliff are still built
+		//past the edge of the heightmap there is no ground, so the sides of the world are built too
+		let mut surrounding_lowest = i32::MAX;
+		for z in cell_z - 1..=cell_z + 1 {
+			for x in cell_x - 1..=cell_x + 1 {
+				surrounding_lowest = surrounding_lowest.min(self.range(level, x, z).0);
+			}
+		}
+		if ((corner.y + size) as i32) <= surrounding_lowest {
+			let mut octant = Octant::new();
+			octant.colour = self.colour(corner.x + size / 2, corner.z + size / 2);
+			octant.normal = pack_normal_u32(Vec3::Y, 1.0);
+			return octant;
+		}
+		if depth == self.max_depth {
+			let mut octant = Octant::new();
+			let normal = self.normal(corner.x, corner.z);
+			octant.colour = self.colour(corner.x, corner.z);
+			octant.normal = pack_normal_u32(normal, 1.0);
+			return octant;
+		}
+
+		let mut node = Node::new();
+		let half = size / 2;
+		for i in 0..OCTANT_COUNT {
+			node.octants[i] = self.fill(dag, dedup, corner + octant_corner(i) * half, depth + 1);
+		}
+		dag.insert_node(dedup, node, depth)
+	}
+
+	fn colour(&self, x: u32, z: u32) -> u32 {
+		let (x, z) = (x.min(self.heightmap.width - 1), z.min(self.heightmap.depth - 1));
+		match self.colour_map {
+			Some(colour_map) => colour_map.colours[(x + z * colour_map.width) as usize],
+			None => pack_f32_u32(LOW_COLOUR.truncate().lerp(Vec3::ONE, self.heightmap.height(x, z)).extend(1.0)),
+		}
+	}
+	//from the slope of the heights in voxels, taken over the neighbouring columns, clamped at the edges
+	fn normal(&self, x: u32, z: u32) -> Vec3 {
+		let height = |x: i64, z: i64| {
+			let (x, z) = (x.clamp(0, self.heightmap.width as i64 - 1), z.clamp(0, self.heightmap.depth as i64 - 1));
+			self.heightmap.height(x as u32, z as u32) * self.vertical_scale
+		};
+		let (x, z) = (x as i64, z as i64);
+		Vec3::new(height(x - 1, z) - height(x + 1, z), 2.0, height(x, z - 1) - height(x, z + 1)).normalize()
+	}
+}
+
+fn octant_corner(i: usize) -> UVec3 {
+	UVec3::new(i as u32 & 1, (i as u32 >> 1) & 1, (i as u32 >> 2) & 1)
+}
+
+impl Raster {
+	fn decode(bytes: &[u8]) -> Result<Self, HeightmapError> {
+		if bytes.starts_with(PNG_MAGIC) {
+			Self::decode_png(bytes)
+		} else if bytes.starts_with(b"P5") || bytes.starts_with(b"P2") || bytes.starts_with(b"P6") {
+			Self::decode_pnm(bytes)
+		} else {
+			Err(HeightmapError::UnsupportedFormat("not a png, pgm or ppm"))
+		}
+	}
+
+	fn decode_png(bytes: &[u8]) -> Result<Self, HeightmapError> {
+		let mut decoder = png::Decoder::new(bytes);
+		//palettes become rgb and low bit depths become 8, 16 bit samples are kept
+		decoder.set_transformations(png::Transformations::EXPAND);
+		let mut reader = decoder.read_info()?;
+		let mut buffer = vec![0; reader.output_buffer_size()];
+		let info = reader.next_frame(&mut buffer)?;
+		let samples = &buffer[..info.buffer_size()];
+		let samples = match info.bit_depth {
+			png::BitDepth::Sixteen => samples.chunks_exact(2).map(|sample| u16::from_be_bytes([sample[0], sample[1]])).collect(),
+			_ => samples.iter().map(|sample| *sample as u16 * 257).collect(),
+		};
+		Ok(Self { width: info.width, height: info.height, channels: info.color_type.samples(), samples })
+	}
+
+	/*
+	binary pgm and ppm, and ascii pgm, the header being whitespace separated with # comments
+	binary samples over 255 are two bytes, most significant first
+	 */
+	fn decode_pnm(bytes: &[u8]) -> Result<Self, HeightmapError> {
+		let mut pos = 2;
+		let mut header = [0u32; 3];
+		for value in header.iter_mut() {
+			*value = next_token(bytes, &mut pos)
+				.and_then(|token| std::str::from_utf8(token).ok()?.parse().ok())
+				.ok_or(HeightmapError::BadHeader("expected width, height and max value"))?;
+		}
+		let [width, height, max_value] = header;
+		if width == 0 || height == 0 {
+			return Err(HeightmapError::BadHeader("no pixels"));
+		}
+		if max_value == 0 || max_value > u16::MAX as u32 {
+			return Err(HeightmapError::BadHeader("max value out of range"));
+		}
+		let channels = if bytes[1] == b'6' { 3 } else { 1 };
+		let count = width as usize * height as usize * channels;
+		let scale = |sample: u32| (sample.min(max_value) * u16::MAX as u32 / max_value) as u16;
+
+		let samples: Vec<u16> = if bytes[1] == b'2' {
+			let mut samples = Vec::with_capacity(count);
+			for _ in 0..count {
+				let sample = next_token(bytes, &mut pos)
+					.and_then(|token| std::str::from_utf8(token).ok()?.parse().ok())
+					.ok_or(HeightmapError::Truncated)?;
+				samples.push(scale(sample));
+			}
+			samples
+		} else {
+			//a single whitespace byte between the header and the samples
+			let body = bytes.get(pos + 1..).ok_or(HeightmapError::Truncated)?;
+			let sample_size = if max_value > u8::MAX as u32 { 2 } else { 1 };
+			if body.len() < count * sample_size {
+				return Err(HeightmapError::Truncated);
+			}
+			body[..count * sample_size].chunks_exact(sample_size)
+				.map(|sample| scale(sample.iter().fold(0, |value, byte| value << 8 | *byte as u32)))
+				.collect()
+		};
+		Ok(Self { width, height, channels, samples })
+	}
+}
+
+//leaves pos on the byte just after the token
+fn next_token<'b>(bytes: &'b [u8], pos: &mut usize) -> Option<&'b [u8]> {
+	loop {
+		match bytes.get(*pos)? {
+			b'#' => {
+				while *bytes.get(*pos)? != b'\n' {
+					*pos += 1;
+				}
+			},
+			byte if byte.is_ascii_whitespace() => *pos += 1,
+			_ => break,
+		}
+	}
+	let start = *pos;
+	while bytes.get(*pos).is_some_and(|byte| !byte.is_ascii_whitespace()) {
+		*pos += 1;
+	}
+	Some(&bytes[start..*pos])
+}
+
+impl fmt::Display for HeightmapError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			HeightmapError::Io(e) => write!(f, "heightmap io error: {}", e),
+			HeightmapError::Png(e) => write!(f, "heightmap png error: {}", e),
+			HeightmapError::UnsupportedFormat(reason) => write!(f, "unsupported heightmap format: {}", reason),
+			HeightmapError::BadHeader(reason) => write!(f, "bad heightmap header: {}", reason),
+			HeightmapError::Truncated => write!(f, "heightmap truncated"),
+			HeightmapError::SizeMismatch { heightmap, colour_map } => write!(f, "colour map is {}x{} but the heightmap is {}x{}", colour_map.0, colour_map.1, heightmap.0, heightmap.1),
+			HeightmapError::TooLarge(extent) => write!(f, "heightmap too large for a dag, extent: {}", extent),
+		}
+	}
+}
+impl std::error::Error for HeightmapError {}
+impl From<std::io::Error> for HeightmapError {
+	fn from(e: std::io::Error) -> Self {
+		HeightmapError::Io(e)
+	}
+}
+impl From<png::DecodingError> for HeightmapError {
+	fn from(e: png::DecodingError) -> Self {
+		HeightmapError::Png(e)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::asset::oct_dag::{NULL_INDEX, unpack_u32_normal};
+
+	//the leaf covering the voxel, however big it is
+	fn voxel(dag: &OctDag, pos: UVec3) -> Octant {
+		let mut octant = Octant{index: 0, ..Octant::new()};
+		for level in (0..dag.max_depth).rev() {
+			if octant.index == NULL_INDEX {
+				break;
+			}
+			let bits = (pos >> level) & UVec3::ONE;
+			octant = dag.nodes[octant.index as usize].octants[(bits.x | bits.y << 1 | bits.z << 2) as usize];
+		}
+		octant
+	}
+
+	#[test]
+	fn decode_binary_pgm() {
+		let mut bytes = b"P5\n# sixteen bit\n2 2\n65535\n".to_vec();
+		bytes.extend_from_slice(&[0x00, 0x00, 0xFF, 0xFF, 0x80, 0x00, 0x00, 0x01]);
+		let heightmap = Heightmap::from_bytes(&bytes).unwrap();
+		assert_eq!((heightmap.width, heightmap.depth), (2, 2));
+		assert_eq!(heightmap.heights, vec![0.0, 1.0, 0x8000 as f32 / 65535.0, 1.0 / 65535.0]);
+		assert!(matches!(Heightmap::from_bytes(&bytes[..bytes.len() - 1]), Err(HeightmapError::Truncated)));
+	}
+
+	#[test]
+	fn decode_ascii_pgm() {
+		let heightmap = Heightmap::from_bytes(b"P2 3 1 # comment\n 4\n0 2\n4").unwrap();
+		assert_eq!((heightmap.width, heightmap.depth), (3, 1));
+		assert_eq!(heightmap.heights, vec![0.0, 32767.0 / 65535.0, 1.0]);
+		assert!(matches!(Heightmap::from_bytes(b"P2 3 1 4 0 2"), Err(HeightmapError::Truncated)));
+	}
+
+	#[test]
+	fn decode_png() {
+		let encode = |colour_type: png::ColorType, bit_depth: png::BitDepth, data: &[u8]| {
+			let mut bytes = Vec::new();
+			let mut encoder = png::Encoder::new(&mut bytes, 2, 1);
+			encoder.set_color(colour_type);
+			encoder.set_depth(bit_depth);
+			encoder.write_header().unwrap().write_image_data(data).unwrap();
+			bytes
+		};
+		let heightmap = Heightmap::from_bytes(&encode(png::ColorType::Grayscale, png::BitDepth::Sixteen, &[0xFF, 0xFF, 0x40, 0x00])).unwrap();
+		assert_eq!(heightmap.heights, vec![1.0, 0x4000 as f32 / 65535.0]);
+		let colour_map = ColourMap::from_bytes(&encode(png::ColorType::Rgb, png::BitDepth::Eight, &[255, 0, 0, 10, 20, 30])).unwrap();
+		assert_eq!(colour_map.colours, vec![0xFF0000FF, 0x0A141EFF]);
+	}
+
+	//a slope up along x, so every top is open on the lower side and built as a single leaf
+	#[test]
+	fn columns_and_normals() {
+		let (width, depth) = (5, 3);
+		let heights = (0..width * depth).map(|i| (i % width) as f32 / (width - 1) as f32).collect();
+		let heightmap = Heightmap { width, depth, heights };
+		let vertical_scale = 6.0;
+		let dag = OctDag::from_heightmap(&heightmap, None, vertical_scale).unwrap();
+		assert!(dag.validate().iter().all(|violation| !violation.is_fatal()));
+		let size = 1 << dag.max_depth;
+		for x in 0..size {
+			for z in 0..size {
+				let top = if x < width && z < depth { (heightmap.height(x, z) * vertical_scale).floor() as u32 } else { 0 };
+				for y in 0..size {
+					let filled = voxel(&dag, UVec3::new(x, y, z)) != Octant::new();
+					assert_eq!(filled, x < width && z < depth && y <= top, "voxel {} {} {}", x, y, z);
+				}
+			}
+		}
+		for x in 0..width {
+			let slope = |x: i64| (x.clamp(0, width as i64 - 1) as f32 / (width - 1) as f32) * vertical_scale;
+			let expected = Vec3::new(slope(x as i64 - 1) - slope(x as i64 + 1), 2.0, 0.0).normalize();
+			let top = voxel(&dag, UVec3::new(x, (heightmap.height(x, 1) * vertical_scale).floor() as u32, 1));
+			assert!(unpack_u32_normal(top.normal).truncate().angle_between(expected) < 0.01, "column {}", x);
+		}
+	}
+}
@@ -0,0 +1,119 @@
+use std::mem::size_of;
+use crate::asset::{oct_dag::{OctDag, Node, Octant}, gc::NodeOrder};
+
+/*
+lossy compaction, merging nodes that point to the same children and only differ slightly in their attributes
+the error of a merge is the size of Node::difference between the node and the one replacing it
+ */
+pub struct MergeReport {
+	pub merged: Vec<u32>, //per depth
+	pub nodes_before: usize,
+	pub nodes_after: usize,
+	pub max_error: f32,
+	pub total_error: f32,
+}
+
+impl OctDag {
+	/*
+	works up from the deepest level so that nodes whose children were merged can then be merged themselves
+	each level is sorted so that nodes with the same children and empty octants sit together, then every node within the threshold of the last kept node is replaced by it
+	this will miss some merges a full comparison would find, but stays n log n on levels with millions of nodes
+	the octants pointing to a merged node keep the aggregate of the original
+	 */
+	pub fn merge_similar(&mut self, threshold: f32) -> MergeReport {
+		let mut report = MergeReport {
+			merged: vec![0; self.max_depth as usize],
+			nodes_before: self.nodes.len(),
+			nodes_after: 0,
+			max_error: 0.0,
+			total_error: 0.0,
+		};
+		let mut remap: Vec<u32> = (0..self.nodes.len() as u32).collect();
+		let levels = self.levels();
+		for (depth, level) in levels.iter().enumerate().skip(1).rev() {
+			for index in level {
+				self.remap_children(*index, &remap);
+			}
+			let mut sorted = level.clone();
+			sorted.sort_by_key(|index| self.nodes[*index as usize].octants.map(|octant| (octant.index, octant == Octant::new(), octant.colour, octant.normal, octant.extra)));
+
+			let mut kept = sorted[0];
+			for index in sorted.into_iter().skip(1) {
+				let error = self.nodes[kept as usize].difference(&self.nodes[index as usize]).abs();
+				if error <= threshold {
+					remap[index as usize] = kept;
+					report.merged[depth] += 1;
+					report.max_error = report.max_error.max(error);
+					report.total_error += error;
+				} else {
+					kept = index;
+				}
+			}
+		}
+		self.remap_children(0, &remap);
+		self.collect_garbage(NodeOrder::Kept);
+		report.nodes_after = self.nodes.len();
+		report
+	}
+}
+
+impl MergeReport {
+	pub fn print_report(&self) {
+		for (depth, merged) in self.merged.iter().enumerate() {
+			if *merged > 0 {
+				println!("Depth: {}, merged: {}", depth, merged);
+			}
+		}
+		let merged: u32 = self.merged.iter().sum();
+		println!("Nodes: {} -> {}, saved Mb: {}, max error: {}, mean error: {}",
+			self.nodes_before, self.nodes_after,
+			((self.nodes_before - self.nodes_after) * size_of::<Node>()) as f32 / 1000000.0,
+			self.max_error, if merged > 0 { self.total_error / merged as f32 } else { 0.0 })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use glam::{Vec3, Vec4};
+	use super::*;
+	use crate::asset::oct_dag::{pack_f32_u32, pack_normal_u32};
+
+	//a root over three nodes of one leaf each, the first two the same and the last with half the red
+	fn three_leaves() -> OctDag {
+		let leaf = |red: f32| {
+			let mut node = Node::new();
+			node.octants[0] = Octant { colour: pack_f32_u32(Vec4::new(red, 0.0, 0.0, 1.0)), normal: pack_normal_u32(Vec3::Y, 1.0), ..Octant::new() };
+			node
+		};
+		let mut root = Node::new();
+		for (octant, index) in root.octants.iter_mut().zip(1..4) {
+			*octant = Octant { index, normal: pack_normal_u32(Vec3::Y, 1.0), ..Octant::new() };
+		}
+		OctDag { nodes: vec![root, leaf(1.0), leaf(1.0), leaf(0.5)], max_depth: 2 }
+	}
+
+	#[test]
+	fn merges_within_threshold() {
+		let mut dag = three_leaves();
+		let report = dag.merge_similar(0.0);
+		assert_eq!(report.merged, vec![0, 1]);
+		assert_eq!((report.nodes_before, report.nodes_after), (4, 3));
+		assert_eq!((report.max_error, report.total_error), (0.0, 0.0));
+		assert_eq!(dag.nodes[0].octants[0].index, dag.nodes[0].octants[1].index);
+		assert_ne!(dag.nodes[0].octants[0].index, dag.nodes[0].octants[2].index);
+
+		let half_red = three_leaves().nodes[1].difference(&three_leaves().nodes[3]).abs();
+		assert!((half_red - 0.5).abs() < 0.01, "{}", half_red);
+		let mut dag = three_leaves();
+		let report = dag.merge_similar(half_red);
+		assert_eq!(report.merged, vec![0, 2]);
+		assert_eq!((report.nodes_before, report.nodes_after), (4, 2));
+		//the half red node sorts first, so both of the others are merged into it
+		assert_eq!((report.max_error, report.total_error), (half_red, half_red * 2.0));
+		assert!(dag.nodes[0].octants[..3].iter().all(|octant| octant.index == 1));
+
+		let report = three_leaves().merge_similar(half_red * 0.99);
+		assert_eq!(report.merged, vec![0, 1]);
+		assert_eq!(report.nodes_after, 3);
+	}
+}
@@ -0,0 +1,285 @@
+use std::{fmt, collections::HashMap};
+use glam::{Vec3, UVec3};
+use crate::{asset::{oct_dag::{OctDag, Octant, pack_normal_u32}, voxel_grid::VoxelGrid}, io::read_bin_file};
+
+/*
+triangle meshes read from obj or binary stl, voxelized into a dag
+the mesh is scaled uniformly so that its longest side spans the whole dag, with no change of axes
+ */
+pub struct Mesh {
+	pub triangles: Vec<[Vec3; 3]>,
+}
+
+#[derive(Debug)]
+pub enum MeshError {
+	Io(std::io::Error),
+	Parse { line: usize, reason: &'static str },
+	Truncated,
+	Empty,
+}
+
+impl Mesh {
+	pub fn load_obj(path: &str) -> Result<Self, MeshError> {
+		Self::from_obj_str(&String::from_utf8_lossy(&read_bin_file(path)?))
+	}
+	pub fn load_stl(path: &str) -> Result<Self, MeshError> {
+		Self::from_stl_bytes(&read_bin_file(path)?)
+	}
+
+	//only positions and faces are read, faces with more than three vertices are split into a fan
+	pub fn from_obj_str(source: &str) -> Result<Self, MeshError> {
+		let mut vertices = Vec::new();
+		let mut triangles = Vec::new();
+		for (i, line) in source.lines().enumerate() {
+			let line_number = i + 1;
+			let mut parts = line.split_whitespace();
+			match parts.next() {
+				Some("v") => {
+					let coords: Vec<f32> = parts.take(3).map(|part| part.parse::<f32>())
+						.collect::<Result<_, _>>()
+						.map_err(|_| MeshError::Parse { line: line_number, reason: "bad vertex" })?;
+					if coords.len() != 3 {
+						return Err(MeshError::Parse { line: line_number, reason: "vertex needs three coordinates" });
+					}
+					vertices.push(Vec3::new(coords[0], coords[1], coords[2]));
+				},
+				Some("f") => {
+					let mut face = Vec::new();
+					for part in parts {
+						//only the position index is needed out of position/texture/normal
+						let index: i64 = part.split('/').next().unwrap_or("").parse()
+							.map_err(|_| MeshError::Parse { line: line_number, reason: "bad face index" })?;
+						let index = if index < 0 { vertices.len() as i64 + index } else { index - 1 };
+						if index < 0 || index as usize >= vertices.len() {
+							return Err(MeshError::Parse { line: line_number, reason: "face index out of bounds" });
+						}
+						face.push(vertices[index as usize]);
+					}
+					if face.len() < 3 {
+						return Err(MeshError::Parse { line: line_number, reason: "face needs three vertices" });
+					}
+					for i in 1..face.len() - 1 {
+						triangles.push([face[0], face[i], face[i + 1]]);
+					}
+				},
+				_ => {},
+			}
+		}
+		Self::new(triangles)
+	}
+
+	//80 byte header, triangle count, then per triangle a normal, three vertices and two attribute bytes
+	pub fn from_stl_bytes(bytes: &[u8]) -> Result<Self, MeshError> {
+		const HEADER_SIZE: usize = 84;
+		const TRIANGLE_SIZE: usize = 50;
+		if bytes.len() < HEADER_SIZE {
+			return Err(MeshError::Truncated);
+		}
+		let count = u32::from_le_bytes(bytes[80..84].try_into().unwrap()) as usize;
+		if bytes.len() < HEADER_SIZE + count * TRIANGLE_SIZE {
+			return Err(MeshError::Truncated);
+		}
+		let read_vec = |offset: usize| {
+			let read = |offset: usize| f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+			Vec3::new(read(offset), read(offset + 4), read(offset + 8))
+		};
+		let triangles = (0..count).map(|i| {
+			let offset = HEADER_SIZE + i * TRIANGLE_SIZE + 12;
+			[read_vec(offset), read_vec(offset + 12), read_vec(offset + 24)]
+		}).collect();
+		Self::new(triangles)
+	}
+
+	fn new(triangles: Vec<[Vec3; 3]>) -> Result<Self, MeshError> {
+		if triangles.is_empty() {
+			return Err(MeshError::Empty);
+		}
+		Ok(Self { triangles })
+	}
+
+	pub fn voxelize(&self, max_depth: u32, colour: u32, solid: bool) -> OctDag {
+		self.voxelize_grid(max_depth, colour, solid).build()
+	}
+
+	/*
+	every voxel that a triangle touches is filled, with the normals of all the triangles in it averaged by area
+	when solid, the inside is found by counting crossings along z through each column of voxel centers
+	 */
+	pub fn voxelize_grid(&self, max_depth: u32, colour: u32, solid: bool) -> VoxelGrid {
+		let mut grid = VoxelGrid::new(max_depth);
+		let size = grid.size() as f32;
+
+		let min = self.triangles.iter().flatten().fold(Vec3::splat(f32::MAX), |min, vertex| min.min(*vertex));
+		let max = self.triangles.iter().flatten().fold(Vec3::splat(f32::MIN), |max, vertex| max.max(*vertex));
+		let scale = size / (max - min).max_element().max(f32::EPSILON);
+		let triangles: Vec<[Vec3; 3]> = self.triangles.iter()
+			.map(|triangle| triangle.map(|vertex| (vertex - min) * scale))
+			.collect();
+
+		let to_voxel = |pos: Vec3| pos.floor().clamp(Vec3::ZERO, Vec3::splat(size - 1.0)).as_uvec3();
+		let mut normals: HashMap<UVec3, Vec3> = HashMap::new();
+		for triangle in &triangles {
+			//left at full length so larger triangles count for more
+			let normal = (triangle[1] - triangle[0]).cross(triangle[2] - triangle[0]);
+			let low = to_voxel(triangle[0].min(triangle[1]).min(triangle[2]));
+			let high = to_voxel(triangle[0].max(triangle[1]).max(triangle[2]));
+			for x in low.x..=high.x {
+				for y in low.y..=high.y {
+					for z in low.z..=high.z {
+						let voxel = UVec3::new(x, y, z);
+						if triangle_box_overlap(triangle, voxel.as_vec3() + Vec3::splat(0.5), Vec3::splat(0.5)) {
+							*normals.entry(voxel).or_insert(Vec3::ZERO) += normal;
+						}
+					}
+				}
+			}
+		}
+		for (voxel, normal) in normals {
+			let normal = normal.normalize_or_zero();
+			let mut octant = Octant::new();
+			octant.colour = colour;
+			octant.normal = pack_normal_u32(normal, 1.0);
+			grid.set(voxel, octant);
+		}
+
+		if solid {
+			let mut crossings: HashMap<(u32, u32), Vec<f32>> = HashMap::new();
+			for triangle in &triangles {
+				let low = to_voxel(triangle[0].min(triangle[1]).min(triangle[2]));
+				let high = to_voxel(triangle[0].max(triangle[1]).max(triangle[2]));
+				for x in low.x..=high.x {
+					for y in low.y..=high.y {
+						if let Some(z) = column_crossing(triangle, x as f32 + 0.5, y as f32 + 0.5) {
+							crossings.entry((x, y)).or_default().push(z);
+						}
+					}
+				}
+			}
+			let mut inside = Octant::new();
+			inside.colour = colour;
+			inside.normal = pack_normal_u32(Vec3::ZERO, 1.0);
+			for ((x, y), mut column) in crossings {
+				column.sort_by(|a, b| a.total_cmp(b));
+				for pair in column.chunks_exact(2) {
+					let start = (pair[0] - 0.5).ceil().max(0.0) as u32;
+					let end = ((pair[1] - 0.5).floor()).min(size - 1.0);
+					if end < 0.0 {
+						continue;
+					}
+					for z in start..=end as u32 {
+						grid.voxels.entry(UVec3::new(x, y, z)).or_insert(inside);
+					}
+				}
+			}
+		}
+		grid
+	}
+}
+
+/*
+separating axis test between a triangle and an axis aligned box
+the axes are the box faces, the triangle normal, and the cross products of the edges with the box faces
+ */
+fn triangle_box_overlap(triangle: &[Vec3; 3], center: Vec3, half_size: Vec3) -> bool {
+	let vertices = triangle.map(|vertex| vertex - center);
+	let edges = [vertices[1] - vertices[0], vertices[2] - vertices[1], vertices[0] - vertices[2]];
+	let separated = |axis: Vec3| {
+		let projections = vertices.map(|vertex| vertex.dot(axis));
+		let radius = half_size.dot(axis.abs());
+		projections[0].min(projections[1]).min(projections[2]) > radius
+			|| projections[0].max(projections[1]).max(projections[2]) < -radius
+	};
+	for box_axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+		if separated(box_axis) {
+			return false;
+		}
+		for edge in edges {
+			if separated(edge.cross(box_axis)) {
+				return false;
+			}
+		}
+	}
+	!separated(edges[0].cross(edges[1]))
+}
+
+/*
+z where the line along z through x, y passes through the triangle, if it does
+a line through an edge or vertex shared by triangles must only cross one of them, or the inside and outside of a column swap
+so a point on an edge only counts for the triangle that has it on its top or left, which is always exactly one of them
+ */
+fn column_crossing(triangle: &[Vec3; 3], x: f32, y: f32) -> Option<f32> {
+	let [a, mut b, mut c] = *triangle;
+	let mut area = edge_function(a, b, c.x, c.y);
+	if area.abs() < f32::EPSILON {
+		return None;
+	}
+	if area < 0.0 {
+		std::mem::swap(&mut b, &mut c);
+		area = -area;
+	}
+	let weights = [(b, c), (c, a), (a, b)].map(|(from, to)| {
+		let weight = edge_function(from, to, x, y);
+		if weight > 0.0 || (weight == 0.0 && top_left(from, to)) { Some(weight / area) } else { None }
+	});
+	match weights {
+		[Some(u), Some(v), Some(w)] => Some(u * a.z + v * b.z + w * c.z),
+		_ => None,
+	}
+}
+//twice the signed area of from, to, point in xy, worked out the same way whichever direction the edge is given in
+fn edge_function(from: Vec3, to: Vec3, x: f32, y: f32) -> f32 {
+	if (from.x, from.y) > (to.x, to.y) {
+		return -edge_function(to, from, x, y);
+	}
+	(to.x - from.x) * (y - from.y) - (to.y - from.y) * (x - from.x)
+}
+//with the triangle wound counter clockwise, the two triangles on an edge go along it in opposite directions
+fn top_left(from: Vec3, to: Vec3) -> bool {
+	let edge = to - from;
+	edge.y > 0.0 || (edge.y == 0.0 && edge.x < 0.0)
+}
+
+impl fmt::Display for MeshError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			MeshError::Io(e) => write!(f, "mesh file io error: {}", e),
+			MeshError::Parse { line, reason } => write!(f, "mesh parse error on line {}: {}", line, reason),
+			MeshError::Truncated => write!(f, "mesh file truncated"),
+			MeshError::Empty => write!(f, "mesh has no triangles"),
+		}
+	}
+}
+impl std::error::Error for MeshError {}
+impl From<std::io::Error> for MeshError {
+	fn from(e: std::io::Error) -> Self {
+		MeshError::Io(e)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const CUBE: &str = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 0\nv 0 0 1\nv 1 0 1\nv 0 1 1\nv 1 1 1\n\
+		f 1 3 4 2\nf 5 6 8 7\nf 1 2 6 5\nf 3 7 8 4\nf 1 5 7 3\nf 2 4 8 6\n";
+
+	//the diagonals of the quads run straight through column centers, so every one of them lands on a shared edge
+	#[test]
+	fn solid_cube_is_filled() {
+		let mesh = Mesh::from_obj_str(CUBE).unwrap();
+		for max_depth in 1..6 {
+			let grid = mesh.voxelize_grid(max_depth, 0xFFFFFFFF, true);
+			assert_eq!(grid.voxels.len(), (grid.size() as usize).pow(3), "depth {}", max_depth);
+		}
+	}
+
+	#[test]
+	fn shared_edges_cross_once() {
+		let quad = [[Vec3::new(0.0, 0.0, 1.0), Vec3::new(2.0, 0.0, 1.0), Vec3::new(2.0, 2.0, 1.0)],
+			[Vec3::new(0.0, 0.0, 1.0), Vec3::new(2.0, 2.0, 1.0), Vec3::new(0.0, 2.0, 1.0)]];
+		for (x, y) in [(0.5, 0.5), (1.0, 1.0), (1.5, 1.5), (0.5, 1.5), (1.0, 0.5)] {
+			let count = quad.iter().filter(|triangle| column_crossing(triangle, x, y).is_some()).count();
+			assert_eq!(count, 1, "column at {}, {}", x, y);
+		}
+	}
+}
@@ -1 +1,20 @@
-pub mod oct_dag;
\ No newline at end of file
+	pub mod oct_dag;
+	pub mod dag_file;
+	pub mod voxel_grid;
+	pub mod vox;
+	pub mod mesh;
+	pub mod density;
+	pub mod split_dag;
+	pub mod lossy;
+	pub mod gc;
+	pub mod compact_dag;
+	pub mod symmetric_dag;
+	pub mod edit;
+	pub mod csg;
+	pub mod raycast;
+	pub mod stats;
+	pub mod validate;
+	pub mod sdf;
+	pub mod noise;
+	pub mod terrain;
+	pub mod heightmap;
\ No newline at end of file
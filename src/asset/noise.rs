@@ -0,0 +1,131 @@
+use glam::{Vec2, Vec3};
+
+/*
+gradient noise after ken perlin's improved noise, with the permutation shuffled from a seed so the same seed always gives the same noise
+values are roughly within -1 to 1, and are 0 on every integer point
+ */
+pub struct Perlin {
+	permutation: [u8; PERMUTATION_SIZE * 2], //doubled so that indexing with a sum of two entries never wraps
+}
+
+const PERMUTATION_SIZE: usize = 256;
+//the most the noise changes over a distance of 1, used to keep distance estimates made from it conservative
+pub const PERLIN_SLOPE: f32 = 2.5;
+
+impl Perlin {
+	pub fn new(seed: u64) -> Self {
+		let mut shuffled: [u8; PERMUTATION_SIZE] = std::array::from_fn(|i| i as u8);
+		let mut state = seed;
+		for i in (1..PERMUTATION_SIZE).rev() {
+			let j = (split_mix(&mut state) % (i as u64 + 1)) as usize;
+			shuffled.swap(i, j);
+		}
+		Self {
+			permutation: std::array::from_fn(|i| shuffled[i % PERMUTATION_SIZE]),
+		}
+	}
+
+	pub fn noise2(&self, pos: Vec2) -> f32 {
+		let cell = pos.floor();
+		let (x, y) = (cell.x as i32 as usize & 255, cell.y as i32 as usize & 255);
+		let local = pos - cell;
+		let fade = local.to_array().map(fade);
+		let p = &self.permutation;
+		let (a, b) = (p[x] as usize + y, p[x + 1] as usize + y);
+
+		let bottom = lerp(fade[0], gradient2(p[a], local), gradient2(p[b], local - Vec2::X));
+		let top = lerp(fade[0], gradient2(p[a + 1], local - Vec2::Y), gradient2(p[b + 1], local - Vec2::ONE));
+		lerp(fade[1], bottom, top)
+	}
+
+	pub fn noise3(&self, pos: Vec3) -> f32 {
+		let cell = pos.floor();
+		let (x, y, z) = (cell.x as i32 as usize & 255, cell.y as i32 as usize & 255, cell.z as i32 as usize & 255);
+		let local = pos - cell;
+		let fade = local.to_array().map(fade);
+		let p = &self.permutation;
+		let (a, b) = (p[x] as usize + y, p[x + 1] as usize + y);
+		let (aa, ab, ba, bb) = (p[a] as usize + z, p[a + 1] as usize + z, p[b] as usize + z, p[b + 1] as usize + z);
+
+		let corner = |hash: u8, offset: Vec3| gradient3(hash, local - offset);
+		lerp(fade[2],
+			lerp(fade[1],
+				lerp(fade[0], corner(p[aa], Vec3::ZERO), corner(p[ba], Vec3::X)),
+				lerp(fade[0], corner(p[ab], Vec3::Y), corner(p[bb], Vec3::new(1.0, 1.0, 0.0)))),
+			lerp(fade[1],
+				lerp(fade[0], corner(p[aa + 1], Vec3::Z), corner(p[ba + 1], Vec3::new(1.0, 0.0, 1.0))),
+				lerp(fade[0], corner(p[ab + 1], Vec3::new(0.0, 1.0, 1.0)), corner(p[bb + 1], Vec3::ONE))))
+	}
+
+	/*
+	fractal brownian motion, octaves of noise each at lacunarity times the frequency and gain times the amplitude of the last
+	divided by the sum of the amplitudes so it stays within the range of a single octave
+	 */
+	pub fn fbm2(&self, pos: Vec2, octaves: u32, lacunarity: f32, gain: f32) -> f32 {
+		let (mut sum, mut amplitude, mut total, mut frequency) = (0.0, 1.0, 0.0, 1.0);
+		for octave in 0..octaves {
+			//each octave is moved so that the zeros on the integer points do not line up
+			sum += self.noise2(pos * frequency + Vec2::splat(octave as f32 * 17.31)) * amplitude;
+			total += amplitude;
+			amplitude *= gain;
+			frequency *= lacunarity;
+		}
+		if total > 0.0 { sum / total } else { 0.0 }
+	}
+	pub fn fbm3(&self, pos: Vec3, octaves: u32, lacunarity: f32, gain: f32) -> f32 {
+		let (mut sum, mut amplitude, mut total, mut frequency) = (0.0, 1.0, 0.0, 1.0);
+		for octave in 0..octaves {
+			sum += self.noise3(pos * frequency + Vec3::splat(octave as f32 * 17.31)) * amplitude;
+			total += amplitude;
+			amplitude *= gain;
+			frequency *= lacunarity;
+		}
+		if total > 0.0 { sum / total } else { 0.0 }
+	}
+}
+
+//how much faster fbm can change than a single octave, for scaling PERLIN_SLOPE
+pub fn fbm_slope(octaves: u32, lacunarity: f32, gain: f32) -> f32 {
+	let (mut slope, mut amplitude, mut total, mut frequency) = (0.0, 1.0, 0.0, 1.0);
+	for _ in 0..octaves {
+		slope += amplitude * frequency;
+		total += amplitude;
+		amplitude *= gain;
+		frequency *= lacunarity;
+	}
+	if total > 0.0 { slope / total } else { 0.0 }
+}
+
+fn split_mix(state: &mut u64) -> u64 {
+	*state = state.wrapping_add(0x9E3779B97F4A7C15);
+	let mut z = *state;
+	z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+	z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+	z ^ (z >> 31)
+}
+fn fade(t: f32) -> f32 {
+	t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+fn lerp(t: f32, a: f32, b: f32) -> f32 {
+	a + t * (b - a)
+}
+//one of eight directions around the circle
+fn gradient2(hash: u8, pos: Vec2) -> f32 {
+	match hash & 7 {
+		0 => pos.x + pos.y,
+		1 => -pos.x + pos.y,
+		2 => pos.x - pos.y,
+		3 => -pos.x - pos.y,
+		4 => pos.x,
+		5 => -pos.x,
+		6 => pos.y,
+		_ => -pos.y,
+	}
+}
+//one of the twelve directions to the edges of a cube, with four repeated to make sixteen
+fn gradient3(hash: u8, pos: Vec3) -> f32 {
+	let h = hash & 15;
+	let u = if h < 8 { pos.x } else { pos.y };
+	let v = if h < 4 { pos.y } else if h == 12 || h == 14 { pos.x } else { pos.z };
+	(if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
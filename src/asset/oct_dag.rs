@@ -1,12 +1,12 @@
-use std::{mem::size_of, hash::{Hash}, cmp::{Eq, Ord, Ordering}, collections::BTreeSet};
+use std::{mem::size_of, hash::{Hash, Hasher, BuildHasherDefault}, cmp::{Eq, Ord, Ordering}, collections::{BTreeSet, HashMap}};
 extern crate glam;
-use glam::{Vec3, Vec4, IVec3, Vec4Swizzles, i32::ivec3};
+use glam::{Vec2, Vec3, Vec4, IVec3, UVec3, Vec3Swizzles, Vec4Swizzles, i32::ivec3};
 
-const NULL_INDEX: u32 = 0xFFFFFFFF;
-const _POSITIVE_X: u32 = 0b001;
-const _POSITIVE_Y: u32 = 0b010;
-const _POSITIVE_Z: u32 = 0b100;
-const OCTANT_COUNT: usize = 8;
+pub const NULL_INDEX: u32 = 0xFFFFFFFF;
+const POSITIVE_X: u32 = 0b001;
+const POSITIVE_Y: u32 = 0b010;
+const POSITIVE_Z: u32 = 0b100;
+pub const OCTANT_COUNT: usize = 8;
 const OCTANT_LIST: [IVec3; 8] = 
 	[ivec3(-1, -1, -1),
 	ivec3(1, -1, -1),
@@ -16,58 +16,133 @@ const OCTANT_LIST: [IVec3; 8] =
 	ivec3(1, -1, 1),
 	ivec3(-1, 1, 1),
 	ivec3(1, 1, 1),];
-const MASK_8BIT: u32 = 0x000000FF;
+pub const MASK_8BIT: u32 = 0x000000FF;
+//constants from view_trace, the half width of the root there and how far light goes through something with half density before 1/e of it is left
+pub const SHADER_SIZE: f32 = 32768.0;
+pub const ABSORPTION_DISTANCE: f32 = 3000.0;
+const MIN_ABSORPTION_DISTANCE: f32 = 0.0001;
 
 type DagAddr = u32;
 #[repr(C)]
-#[derive(Clone, Copy, Debug, Hash)]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
 pub struct Octant {
 	pub index: DagAddr, //index of the next node
 	pub colour: u32, //rgba
-	pub normal: u32, //xyz desnity //change name to volume, or physical
+	pub normal: u32, //octahedral xyz, density, see pack_normal_u32 //change name to volume, or physical
 	pub extra: u32, // 8 shine, 8 radiance, 16 or 8 frames, 
 }
 #[repr(C)]
-#[derive(Clone, Copy, Debug, Hash)]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
 pub struct Node {
 	pub octants: [Octant; OCTANT_COUNT],
 }
 pub struct OctDag {
 	pub nodes: Vec<Node>,
+	pub max_depth: u32,
+}
+
+/*
+nodes are only ever shared with other nodes of the same depth, so each level gets its own table
+keyed on the nodes dedup key, and the hits and misses are kept so the amount of reuse can be reported
+ */
+pub struct DedupTable {
+	levels: Vec<HashMap<Node, DagAddr, BuildHasherDefault<NodeHasher>>>,
+	pub hits: Vec<u32>,
+	pub misses: Vec<u32>,
 }
 
 
 //TODO: consider making fn that creates u8 representation of data for render
-//TODO: consider making it possible to generate each virst octant in seperate threads
 impl OctDag {
-	pub fn new_from_fn(max_depth: u32) -> Self {
-		
-		todo!();
+	pub fn new_from_fn<V, C>(max_depth: u32, volume: V, colour: C) -> Self 
+	where V: Fn(Vec3, f32) -> Vec4, C: Fn(IVec3, i32) -> Octant {
+		Self::new_from_source(max_depth, &FnSource{volume, colour})
 	}
 
-	pub fn new_test(dag_type: TestDagType, max_depth: u32) -> Self {
+	pub fn new_from_source<S: VolumeSource + ?Sized>(max_depth: u32, source: &S) -> Self {
+		Self::new_from_source_with_report(max_depth, source).0
+	}
+
+	pub fn new_from_source_with_report<S: VolumeSource + ?Sized>(max_depth: u32, source: &S) -> (Self, DedupTable) {
 		if max_depth > 16  {
 			panic!("depth out of bounds");
 		}
 		
-		let mut dag = OctDag{nodes: Vec::<Node>::new()};
-		let mut level_list = Vec::<Vec::<u32>>::new();
-		let vol_list = dag_type.new(max_depth);
-		level_list.resize((max_depth) as usize, Vec::<u32>::new());
+		let mut dag = OctDag{nodes: Vec::<Node>::new(), max_depth};
+		let mut dedup = DedupTable::new(max_depth);
 		dag.nodes.push(Node::new());
 
 		let next_level_size = i32::pow(2, max_depth - 1);
 		for i in 0..8 {
-			dag.nodes[0].octants[i] = dag.fill_oct( vol_list,
-				&mut level_list,  OCTANT_LIST[i] * IVec3::splat(next_level_size), 1, max_depth); 
+			dag.nodes[0].octants[i] = dag.fill_oct(source,
+				&mut dedup,  OCTANT_LIST[i] * IVec3::splat(next_level_size), 1, max_depth); 
 		}
 
-		return dag;
+		return (dag, dedup);
 	}
 
+	pub fn new_from_source_parallel<S: VolumeSource + Sync + ?Sized>(max_depth: u32, source: &S, threads: usize) -> Self {
+		Self::new_from_source_parallel_with_report(max_depth, source, threads).0
+	}
 
-	fn fill_oct(&mut self, volumes: &[(VolumeType, ColourType)], 
-		level_list: &mut Vec<Vec<u32>>, 
+	/*
+	each first level octant is built on its own pool and table, spread over the threads
+	they are then merged back in octant order, which gives the exact same pool as building on one thread
+	 */
+	pub fn new_from_source_parallel_with_report<S: VolumeSource + Sync + ?Sized>(max_depth: u32, source: &S, threads: usize) -> (Self, DedupTable) {
+		if max_depth > 16  {
+			panic!("depth out of bounds");
+		}
+		let threads = threads.clamp(1, OCTANT_COUNT);
+		let next_level_size = i32::pow(2, max_depth - 1);
+
+		let mut subtrees: Vec<Option<(OctDag, DedupTable, Octant)>> = (0..OCTANT_COUNT).map(|_| None).collect();
+		std::thread::scope(|scope| {
+			let workers: Vec<_> = (0..threads).map(|thread| {
+				scope.spawn(move || {
+					let mut built = Vec::new();
+					for i in (thread..OCTANT_COUNT).step_by(threads) {
+						let mut subtree = OctDag{nodes: Vec::<Node>::new(), max_depth};
+						let mut dedup = DedupTable::new(max_depth);
+						let octant = subtree.fill_oct(source,
+							&mut dedup, OCTANT_LIST[i] * IVec3::splat(next_level_size), 1, max_depth);
+						built.push((i, (subtree, dedup, octant)));
+					}
+					built
+				})
+			}).collect();
+			for worker in workers {
+				for (i, subtree) in worker.join().expect("dag build thread panicked") {
+					subtrees[i] = Some(subtree);
+				}
+			}
+		});
+
+		let mut dag = OctDag{nodes: vec![Node::new()], max_depth};
+		let mut dedup = DedupTable::new(max_depth);
+		for (i, subtree) in subtrees.into_iter().enumerate() {
+			let (subtree, subtree_dedup, mut octant) = subtree.unwrap();
+			let remap = dedup.merge(&mut dag.nodes, &subtree.nodes, &subtree_dedup);
+			if octant.index != NULL_INDEX {
+				octant.index = remap[octant.index as usize];
+			}
+			dag.nodes[0].octants[i] = octant;
+		}
+		(dag, dedup)
+	}
+
+	pub fn new_test(dag_type: TestDagType, max_depth: u32) -> Self {
+		Self::new_test_with_report(dag_type, max_depth).0
+	}
+
+	pub fn new_test_with_report(dag_type: TestDagType, max_depth: u32) -> (Self, DedupTable) {
+		let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+		Self::new_from_source_parallel_with_report(max_depth, dag_type.new(max_depth), threads)
+	}
+
+
+	fn fill_oct<S: VolumeSource + ?Sized>(&mut self, source: &S, 
+		dedup: &mut DedupTable, 
 		pos: IVec3, 
 		depth: u32, 
 		max_depth: u32) -> Octant {
@@ -77,19 +152,7 @@ impl OctDag {
 		let mut octant = Octant::new();
 		let mut node = Node::new();
 
-		/*
-		run through the volume functions
-		find the deepest and store it and the associated colour function
-		 */
-		let mut funcs = volumes[0];
-		let mut max_vol = volumes[0].0.new(pos.as_vec3(), max_level_size as f32);
-		for i in volumes {
-			let vol = i.0.new(pos.as_vec3(), max_level_size as f32);
-			if vol.x < max_vol.x {
-				funcs = *i;
-				max_vol = vol;
-			}
-		}
+		let mut max_vol = source.volume(pos.as_vec3(), max_level_size as f32);
 		
 		//figuring out how far a point is inside a volume in relation to the level size
 		//remeber that x holds the distance inside, while the remainder is the normal
@@ -97,94 +160,24 @@ impl OctDag {
 
 		if max_vol.x < -1.0 || (depth == max_depth && max_vol.x <= 1.0) {
 			//calling colour function
-			octant = funcs.1.new(pos, max_level_size);
+			octant = source.colour(pos, max_level_size);
+			//a leaf never points anywhere, whatever the colour function handed back
+			octant.index = NULL_INDEX;
 
-			let norm_len = max_vol.yzw().length();
-			max_vol.y /= norm_len;
-			max_vol.z /= norm_len;
-			max_vol.w /= norm_len;
-
-			//max_vol.yzw() = max_vol.yzw().normalize_or_ZERO;
-
-			octant.normal = pack_f32_u32(Vec4::new(max_vol.y, max_vol.z, max_vol.w, 1.0));
+			octant.normal = pack_normal_u32(max_vol.yzw(), 1.0);
 
 		} else if max_vol.x <= 1.0  {
 			let next_depth = depth + 1;
 			let next_level_size = i32::pow(2, max_depth - next_depth);
 			for i in 0..8 {
-				node.octants[i] = self.fill_oct(volumes, level_list, pos + OCTANT_LIST[i] * IVec3::splat(next_level_size), next_depth, max_depth);
-			}
-
-			match level_list[depth as usize].iter().find(|x| {
-				let mut is_same = true;
-				for i in 0..OCTANT_COUNT {
-					if node.octants[i].index == NULL_INDEX {
-						if is_same && !(self.nodes[**x as usize].octants[i].index == NULL_INDEX && self.nodes[**x as usize].octants[i].colour == node.octants[i].colour) {
-							is_same = false;
-						}
-					} else {
-						if is_same && !(self.nodes[**x as usize].octants[i].index == node.octants[i].index) {
-							is_same = false;
-						}
-					}
-				}
-				return is_same; 
-			}) {
-				Some(x) => {
-					octant.index = *x;
-				}
-				None => {
-					self.nodes.push(node);
-					level_list[depth as usize].push(self.nodes.len() as u32 - 1);
-					octant.index = self.nodes.len() as u32 - 1;
-				}
+				node.octants[i] = self.fill_oct(source, dedup, pos + OCTANT_LIST[i] * IVec3::splat(next_level_size), next_depth, max_depth);
 			}
 
-			let (mut r, mut g, mut b, mut a) = (0.0, 0.0, 0.0, 0.0);
-			let (mut x, mut y, mut z, mut density) = (0.0, 0.0, 0.0, 0.0);
-
-			/*
-			consider changing so that when something like a flat plane has a see through alpha, it is reduced
-			currently as walls go up in level they keep their exact alpha, but keep they get thicker, meaning transmissability comes down
-			 */
-			let filter = |a: usize, b: usize,  arr: &mut [Octant; 8]| {
-				if arr[a].normal & MASK_8BIT > arr[b].normal & MASK_8BIT {
-					let temp = arr[a];
-					arr[a] = arr[b];
-					arr[b] = temp;
-				}
-			};
-			let mut octants_copy = node.octants.clone();
-			for i in 0..4 {
-				filter(i * 2, i * 2 + 1, &mut octants_copy);	
-			}
-			for i in 0..2 {
-				filter(i * 4, i * 4 + 2, &mut octants_copy);
-			}
-			for i in 0..2 {
-				filter(i * 4 + 1, i * 4 + 3, &mut octants_copy);
-			}
-			for i in 0..2 {
-				filter(i * 4 + 2, i * 4 + 3, &mut octants_copy);
-				density += (octants_copy[i * 4 + 2].normal & MASK_8BIT) as f32 / 255.0 + (octants_copy[i * 4 + 3].normal & MASK_8BIT) as f32 / 255.0;
+			//volumes that only give a bound on the distance can send it down into octants with nothing in them
+			if !source.distance_is_bound() || node.octants.iter().any(|child| *child != Octant::new()) {
+				octant = node.aggregate(depth);
+				octant.index = dedup.insert(&mut self.nodes, node, depth);
 			}
-
-			let mut cummulative_denisty = 0.0;
-			for i in 0..OCTANT_COUNT {
-				let colour = unpack_u32_f32(node.octants[i].colour);
-				let normal = unpack_u32_f32(node.octants[i].normal);
-				cummulative_denisty += normal.w;
-				r += colour.x * normal.w;
-				g += colour.y * normal.w;
-				b += colour.z * normal.w;
-				a += colour.w * normal.w;
-
-				x += normal.x * normal.w;
-				y += normal.y * normal.w;
-				z += normal.z * normal.w;
-			}
-			octant.colour = pack_f32_u32(Vec4::new(r / cummulative_denisty, g / cummulative_denisty, b / cummulative_denisty, a / cummulative_denisty));
-			octant.normal = pack_f32_u32(Vec4::new(x / cummulative_denisty, y / cummulative_denisty, z / cummulative_denisty, density));
 		}
 		return octant;
 	}
@@ -200,12 +193,112 @@ impl OctDag {
 			}
 		}
 	}
+	//the octants within an octant, a leaf being split into eight of itself
+	pub fn children(&self, octant: Octant) -> [Octant; OCTANT_COUNT] {
+		if octant.index == NULL_INDEX {
+			[octant; OCTANT_COUNT]
+		} else {
+			self.nodes[octant.index as usize].octants
+		}
+	}
+	//the leaf covering a voxel, positions being the same as VoxelGrid, empty where there is nothing
+	pub fn voxel(&self, pos: UVec3) -> Octant {
+		let mut octant = Octant{index: 0, ..Octant::new()};
+		for level in (0..self.max_depth).rev() {
+			if octant.index == NULL_INDEX {
+				break;
+			}
+			let bits = (pos >> level) & UVec3::ONE;
+			octant = self.nodes[octant.index as usize].octants[(bits.x | bits.y << 1 | bits.z << 2) as usize];
+		}
+		octant
+	}
+	/*
+	the octant pointing to the node once it is added to the pool, with the aggregate the same as fill_oct
+	nodes with nothing in them become empty octants, and nodes that are all the same leaf become that leaf
+	the node is only shared with one that is exactly the same, so that changes to any part of an octant are kept
+	 */
+	pub fn insert_node(&mut self, dedup: &mut DedupTable, node: Node, depth: u32) -> Octant {
+		if node.octants.iter().all(|octant| *octant == Octant::new()) {
+			return Octant::new();
+		}
+		if let Some(leaf) = node.uniform_leaf() {
+			return leaf;
+		}
+		let mut octant = node.aggregate(depth);
+		octant.index = dedup.insert_exact(&mut self.nodes, node, depth);
+		octant
+	}
+	//the nodes reachable at each depth, each node listed only at the first depth it is found at
+	pub fn levels(&self) -> Vec<Vec<u32>> {
+		let mut visited = vec![false; self.nodes.len()];
+		let mut levels = vec![vec![0]];
+		visited[0] = true;
+		loop {
+			let mut next = Vec::new();
+			for index in levels.last().unwrap() {
+				for octant in self.nodes[*index as usize].octants {
+					if octant.index != NULL_INDEX && !visited[octant.index as usize] {
+						visited[octant.index as usize] = true;
+						next.push(octant.index);
+					}
+				}
+			}
+			if next.is_empty() {
+				break;
+			}
+			levels.push(next);
+		}
+		levels
+	}
+
+	//edge length of the whole volume in build units, leaves being two units wide
+	pub fn world_size(&self) -> u32 {
+		1 << (self.max_depth + 1)
+	}
 	pub fn print_size(&self) {
-		println!("Node count: {},  Size in Mb: {}", self.nodes.len(), (self.nodes.len() * size_of::<Node>()) as f32 / 1000000.0)
+		let size = self.nodes.len() * size_of::<Node>();
+		let compact_size = self.nodes.iter().map(|node| node.compact_words() as usize).sum::<usize>() * size_of::<u32>();
+		println!("Node count: {},  Size in Mb: {}", self.nodes.len(), size as f32 / 1000000.0);
+		println!("Compact size in Mb: {}, {:.2}% of the node layout", compact_size as f32 / 1000000.0, compact_size as f32 / size as f32 * 100.0)
 	}
 }
 
 
+/*
+the source of the geometry and colour that a dag is built from
+volume returns the signed distance in x, with negative values being inside, and the direction of the normal in yzw
+colour is only called for leaves, and only the colour and extra of the returned octant are kept
+ */
+pub trait VolumeSource {
+	fn volume(&self, pos: Vec3, max_level_size: f32) -> Vec4;
+	fn colour(&self, pos: IVec3, max_level_size: i32) -> Octant;
+	//true when the distance is less than how far the surface really is, so that nodes with nothing in them are left out rather than kept
+	fn distance_is_bound(&self) -> bool {
+		false
+	}
+}
+//how far around a point the distance is sampled for the gradient, a quarter of a leaf
+const GRADIENT_STEP: f32 = 0.5;
+//pointing the way the distance grows, not normalized, from the four corners of a tetrahedron which needs one less sample than going both ways along each axis
+pub fn tetrahedron_gradient(distance: impl Fn(Vec3) -> f32, pos: Vec3) -> Vec3 {
+	let corners = [Vec3::new(1.0, -1.0, -1.0), Vec3::new(-1.0, -1.0, 1.0), Vec3::new(-1.0, 1.0, -1.0), Vec3::new(1.0, 1.0, 1.0)];
+	corners.iter().map(|corner| *corner * distance(pos + *corner * GRADIENT_STEP)).sum()
+}
+/*
+what volume returns for a source with a true distance, or an estimate of one
+fill_oct takes the distance as being to the nearest point along any one axis, cutting off octants more than their half size away
+true distances have to be made smaller by the most that can be along a diagonal, root 3, for nothing to be cut off
+ */
+pub fn true_distance_volume(distance: f32, gradient: Vec3) -> Vec4 {
+	(distance / 3f32.sqrt(), gradient).into()
+}
+pub struct FnSource<V, C> 
+where V: Fn(Vec3, f32) -> Vec4, C: Fn(IVec3, i32) -> Octant {
+	pub volume: V,
+	pub colour: C,
+}
+
 pub enum TestDagType {
 	Box,
 	Pillar,
@@ -224,6 +317,144 @@ pub enum ColourType {
 	ColouredWalls,
 }
 
+impl DedupTable {
+	pub fn new(max_depth: u32) -> Self {
+		Self {
+			levels: vec![HashMap::default(); max_depth as usize],
+			hits: vec![0; max_depth as usize],
+			misses: vec![0; max_depth as usize],
+		}
+	}
+	//returns the index of an identical node already at this depth, otherwise pushes the node onto the pool
+	pub fn insert(&mut self, nodes: &mut Vec<Node>, node: Node, depth: u32) -> DagAddr {
+		let depth = depth as usize;
+		let key = node.dedup_key();
+		match self.levels[depth].get(&key) {
+			Some(index) => {
+				self.hits[depth] += 1;
+				*index
+			},
+			None => {
+				let index = nodes.len() as DagAddr;
+				nodes.push(node);
+				self.levels[depth].insert(key, index);
+				self.misses[depth] += 1;
+				index
+			},
+		}
+	}
+	/*
+	the same as insert, but the node is only reused when it is identical, normals and extras included
+	for changing a dag that is already built, where sharing on the key alone would undo an edit to only the normals or extras
+	a node that is not reused is left out of the table if its key is already taken
+	 */
+	pub fn insert_exact(&mut self, nodes: &mut Vec<Node>, node: Node, depth: u32) -> DagAddr {
+		let depth = depth as usize;
+		let key = node.dedup_key();
+		if let Some(index) = self.levels[depth].get(&key) {
+			if nodes[*index as usize] == node {
+				self.hits[depth] += 1;
+				return *index;
+			}
+		}
+		let index = nodes.len() as DagAddr;
+		nodes.push(node);
+		self.levels[depth].entry(key).or_insert(index);
+		self.misses[depth] += 1;
+		index
+	}
+	/*
+	adds every node of another pool built with its own table, returning where each of its nodes ended up
+	the other pool has to have children before their parents, which is always the case for pools built by fill_oct
+	 */
+	pub fn merge(&mut self, nodes: &mut Vec<Node>, other_nodes: &[Node], other: &DedupTable) -> Vec<DagAddr> {
+		let mut depths = vec![0; other_nodes.len()];
+		for (depth, level) in other.levels.iter().enumerate() {
+			for index in level.values() {
+				depths[*index as usize] = depth as u32;
+			}
+		}
+		let mut remap = Vec::with_capacity(other_nodes.len());
+		for (node, depth) in other_nodes.iter().zip(depths) {
+			let mut node = *node;
+			for octant in node.octants.iter_mut() {
+				if octant.index != NULL_INDEX {
+					octant.index = remap[octant.index as usize];
+				}
+			}
+			remap.push(self.insert(nodes, node, depth));
+		}
+		for depth in 0..self.levels.len() {
+			self.hits[depth] += other.hits[depth];
+		}
+		remap
+	}
+	//a table holding every node already in the dag, so that more can be added to it without duplicates
+	pub fn from_dag(dag: &OctDag) -> Self {
+		let mut dedup = Self::new(dag.max_depth);
+		for (depth, level) in dag.levels().into_iter().enumerate().skip(1) {
+			for index in level {
+				dedup.levels[depth].entry(dag.nodes[index as usize].dedup_key()).or_insert(index);
+				dedup.misses[depth] += 1;
+			}
+		}
+		dedup
+	}
+	pub fn print_report(&self) {
+		for depth in 0..self.levels.len() {
+			let total = self.hits[depth] + self.misses[depth];
+			if total > 0 {
+				println!("Depth: {}, hits: {}, misses: {}, reuse: {:.2}%", depth, self.hits[depth], self.misses[depth], self.hits[depth] as f32 / total as f32 * 100.0);
+			}
+		}
+	}
+}
+
+/*
+nodes are nothing but u32s, so a simple multiplicative hash is plenty and is much faster than the default
+ */
+#[derive(Default)]
+pub struct NodeHasher {
+	hash: u64,
+}
+impl Hasher for NodeHasher {
+	fn write(&mut self, bytes: &[u8]) {
+		for byte in bytes {
+			self.write_u64(*byte as u64);
+		}
+	}
+	fn write_u32(&mut self, value: u32) {
+		self.write_u64(value as u64);
+	}
+	fn write_u64(&mut self, value: u64) {
+		self.hash = (self.hash.rotate_left(5) ^ value).wrapping_mul(0x517cc1b727220a95);
+	}
+	fn finish(&self) -> u64 {
+		self.hash
+	}
+}
+
+impl<V, C> VolumeSource for FnSource<V, C> 
+where V: Fn(Vec3, f32) -> Vec4, C: Fn(IVec3, i32) -> Octant {
+	fn volume(&self, pos: Vec3, max_level_size: f32) -> Vec4 {
+		(self.volume)(pos, max_level_size)
+	}
+	fn colour(&self, pos: IVec3, max_level_size: i32) -> Octant {
+		(self.colour)(pos, max_level_size)
+	}
+}
+/*
+run through the volume functions
+find the deepest and use it and the associated colour function
+ */
+impl VolumeSource for [(VolumeType, ColourType)] {
+	fn volume(&self, pos: Vec3, max_level_size: f32) -> Vec4 {
+		deepest_volume(self, pos, max_level_size).0
+	}
+	fn colour(&self, pos: IVec3, max_level_size: i32) -> Octant {
+		deepest_volume(self, pos.as_vec3(), max_level_size as f32).1.1.new(pos, max_level_size)
+	}
+}
 impl TestDagType {
 	pub fn new(&self, depth: u32) -> &[(VolumeType, ColourType)] {
 		match self {
@@ -347,6 +578,77 @@ impl Node {
 	pub fn new() -> Node {
 		return Node{octants: [Octant::new(); 8]};
 	}
+	/*
+	the node with everything zeroed that is not considered when checking if two nodes can be shared
+	leaf octants match on colour, while branch octants only need to point to the same node
+	 */
+	pub fn dedup_key(&self) -> Node {
+		let mut key = Node::new();
+		for i in 0..OCTANT_COUNT {
+			key.octants[i].index = self.octants[i].index;
+			if self.octants[i].index == NULL_INDEX {
+				key.octants[i].colour = self.octants[i].colour;
+			}
+		}
+		key
+	}
+	/*
+	the colour and normal of the octant pointing to this node, to be used when it is at or past the lod cut off
+	colours and normals are averaged weighted by density
+	the density is the one that lets through as much light on average as the octants do, so things look as see through from far away as up close
+	light through the node is averaged over the four columns of two octants along each axis, then the optical depths of the axes are averaged
+	averaging optical depth keeps a wall solid if it blocks any one axis, while a see through slab comes out at about its real thickness
+	depth is of the node, the same as given to the dedup table, as how much light gets through an octant depends on how big it is
+	 */
+	pub fn aggregate(&self, depth: u32) -> Octant {
+		let (mut r, mut g, mut b, mut a) = (0.0, 0.0, 0.0, 0.0);
+		let (mut x, mut y, mut z) = (0.0, 0.0, 0.0);
+
+		let octant_width = SHADER_SIZE / (1u32 << depth) as f32;
+		let mut transmittances = [0.0; OCTANT_COUNT];
+		let mut cummulative_denisty = 0.0;
+		for (transmittance, octant) in transmittances.iter_mut().zip(self.octants) {
+			let colour = unpack_u32_f32(octant.colour);
+			let normal = unpack_u32_normal(octant.normal);
+			*transmittance = if normal.w > 0.0 && colour.w > 0.0 { (-octant_width / absorption_distance(colour.w, normal.w)).exp() } else { 1.0 };
+			cummulative_denisty += normal.w;
+			r += colour.x * normal.w;
+			g += colour.y * normal.w;
+			b += colour.z * normal.w;
+			a += colour.w * normal.w;
+
+			x += normal.x * normal.w;
+			y += normal.y * normal.w;
+			z += normal.z * normal.w;
+		}
+		if cummulative_denisty == 0.0 {
+			return Octant::new();
+		}
+		let mut optical_depth = 0.0;
+		for axis in [POSITIVE_X, POSITIVE_Y, POSITIVE_Z] {
+			let columns: f32 = (0..OCTANT_COUNT as u32).filter(|i| i & axis == 0)
+				.map(|i| transmittances[i as usize] * transmittances[(i | axis) as usize]).sum();
+			optical_depth -= (columns / 4.0).ln() / 3.0;
+		}
+		let alpha = a / cummulative_denisty;
+
+		let mut octant = Octant::new();
+		octant.colour = pack_f32_u32(Vec4::new(r / cummulative_denisty, g / cummulative_denisty, b / cummulative_denisty, alpha));
+		octant.normal = pack_normal_u32(Vec3::new(x, y, z), 0.0) | density_byte(alpha, optical_depth / (octant_width * 2.0));
+		octant
+	}
+	//the leaf this node can be replaced with if all its octants are the same filled leaf
+	pub fn uniform_leaf(&self) -> Option<Octant> {
+		let first = self.octants[0];
+		if first.index != NULL_INDEX || first == Octant::new() {
+			return None;
+		}
+		if self.octants[1..OCTANT_COUNT].iter().all(|octant| *octant == first) {
+			Some(first)
+		} else {
+			None
+		}
+	}
 	/*
 	   a possible option for reuse is the culling of inefficent nodes
 		suppose a node has one octant that only has an extremely small amount of leaf voxels and should be culled to a leaf node
@@ -358,13 +660,18 @@ impl Node {
 			} else if x.index < y.index {
 				return std::f32::MIN; 
 			}
+			//an empty octant and a filled leaf share the null index but are not the same shape
+			let (x_empty, y_empty) = (x == Octant::new(), y == Octant::new());
+			if x_empty != y_empty {
+				return if x_empty { f32::MIN } else { f32::MAX };
+			}
 		}
 		let mut positive = Vec4::ZERO;
 		let mut negative = Vec4::ZERO;
 		for (x, y) in self.octants.into_iter().zip(other.octants.into_iter()) {
 			let rgba_delta = unpack_u32_f32(x.colour) - unpack_u32_f32(y.colour);
-			let normal_delta = unpack_u32_f32(x.colour) - unpack_u32_f32(y.colour);
-			let extra_delta = unpack_u32_f32(x.colour) - unpack_u32_f32(y.colour);
+			let normal_delta = unpack_u32_normal(x.normal) - unpack_u32_normal(y.normal);
+			let extra_delta = unpack_u32_f32(x.extra) - unpack_u32_f32(y.extra);
 			positive += rgba_delta.max(Vec4::ZERO) + normal_delta.max(Vec4::ZERO) + extra_delta.max(Vec4::ZERO);
 			negative += rgba_delta.min(Vec4::ZERO) + normal_delta.min(Vec4::ZERO) + extra_delta.min(Vec4::ZERO);
 		}
@@ -383,23 +690,246 @@ impl Octant {
 	}
 }
 
-fn unpack_u32_f32(data: u32) -> Vec4 {
+fn deepest_volume(volumes: &[(VolumeType, ColourType)], pos: Vec3, max_level_size: f32) -> (Vec4, (VolumeType, ColourType)) {
+	let mut funcs = volumes[0];
+	let mut max_vol = volumes[0].0.new(pos, max_level_size);
+	for i in volumes {
+		let vol = i.0.new(pos, max_level_size);
+		if vol.x < max_vol.x {
+			funcs = *i;
+			max_vol = vol;
+		}
+	}
+	(max_vol, funcs)
+}
+/*
+how far light goes in shader units through a leaf of any size before 1/e of it is left, the same as in view_trace
+full density is solid, half density is ABSORPTION_DISTANCE, and the lowest density goes 254 times that
+ */
+pub fn absorption_distance(alpha: f32, density: f32) -> f32 {
+	(ABSORPTION_DISTANCE * ((1.0 - density) / density + (1.0 - alpha))).max(MIN_ABSORPTION_DISTANCE)
+}
+//the density with the absorption distance closest to 1 / extinction, never 0 as that would mean there is nothing there
+fn density_byte(alpha: f32, extinction: f32) -> u32 {
+	if extinction <= 0.0 || alpha <= 0.0 {
+		return 1;
+	}
+	let thinness = (1.0 / (extinction * ABSORPTION_DISTANCE) - (1.0 - alpha)).max(0.0);
+	((255.0 / (1.0 + thinness)).round() as u32).clamp(1, MASK_8BIT)
+}
+/*
+normals take the top 24 bits as two signed 12 bit coordinates on an octahedron folded out onto a square, with the density in the low 8 bits
+the octahedron puts about the same number of points everywhere on the sphere, and a signed encoding keeps the axes and 0 exact
+a zero normal has no direction to keep and comes back as +z
+ */
+const NORMAL_BITS: u32 = 12;
+const NORMAL_MASK: u32 = (1 << NORMAL_BITS) - 1;
+const NORMAL_MAX: f32 = ((1 << (NORMAL_BITS - 1)) - 1) as f32;
+pub fn pack_normal_u32(normal: Vec3, density: f32) -> u32 {
+	let density = (density * 255.0).round() as u8 as u32;
+	let octahedron = normal / (normal.x.abs() + normal.y.abs() + normal.z.abs());
+	if !octahedron.is_finite() {
+		return density;
+	}
+	let folded = if octahedron.z < 0.0 {
+		//the lower half is folded out over the corners of the square
+		(Vec2::ONE - Vec2::new(octahedron.y, octahedron.x).abs()) * sign_not_zero(octahedron.xy())
+	} else {
+		octahedron.xy()
+	};
+	//rounding each coordinate on its own is not always the nearest once unfolded, so the closest of the four points around it is used
+	let fixed = folded.clamp(Vec2::NEG_ONE, Vec2::ONE) * NORMAL_MAX;
+	let to_bits = |value: f32| (value as i32 as u32) & NORMAL_MASK;
+	let mut best = (density, f32::MIN);
+	for x in [fixed.x.floor(), fixed.x.ceil()] {
+		for y in [fixed.y.floor(), fixed.y.ceil()] {
+			let bits = to_bits(x) << (NORMAL_BITS + 8) | to_bits(y) << 8 | density;
+			let alignment = unpack_u32_normal(bits).truncate().dot(normal);
+			if alignment > best.1 {
+				best = (bits, alignment);
+			}
+		}
+	}
+	best.0
+}
+//the normal, unit length, with the density in w
+pub fn unpack_u32_normal(data: u32) -> Vec4 {
+	//shifting the sign bit of each coordinate up to the top then back down carries it through the rest
+	let from_bits = |shift: u32| ((((data >> shift) & NORMAL_MASK) << (32 - NORMAL_BITS)) as i32 >> (32 - NORMAL_BITS)) as f32 / NORMAL_MAX;
+	let folded = Vec2::new(from_bits(NORMAL_BITS + 8), from_bits(8)).clamp(Vec2::NEG_ONE, Vec2::ONE);
+	let z = 1.0 - folded.x.abs() - folded.y.abs();
+	let xy = if z < 0.0 { (Vec2::ONE - Vec2::new(folded.y, folded.x).abs()) * sign_not_zero(folded) } else { folded };
+	Vec3::new(xy.x, xy.y, z).normalize().extend((data & MASK_8BIT) as f32 / 255.0)
+}
+fn sign_not_zero(value: Vec2) -> Vec2 {
+	Vec2::new(if value.x < 0.0 { -1.0 } else { 1.0 }, if value.y < 0.0 { -1.0 } else { 1.0 })
+}
+pub fn unpack_u32_f32(data: u32) -> Vec4 {
 	let bytes = unpack_u8_u32(data);
 	Vec4::new(bytes.0 as f32 / 255.0, bytes.1 as f32 / 255.0, bytes.2 as f32 / 255.0, bytes.3 as f32 / 255.0)
 }
 //assumed that the floats passed in are < 1
-fn pack_f32_u32(data: Vec4) -> u32 {
+pub fn pack_f32_u32(data: Vec4) -> u32 {
 	pack_u8_u32(((data.x * 255.0) as u8, (data.y * 255.0) as u8, (data.z * 255.0) as u8, (data.w * 255.0) as u8))
 }
-fn unpack_u8_u32(data: u32) -> (u8, u8, u8, u8) {
+pub fn unpack_u8_u32(data: u32) -> (u8, u8, u8, u8) {
 	((data >> 24 & MASK_8BIT) as u8,
 	(data >> 16 & MASK_8BIT) as u8,
 	(data >> 8 & MASK_8BIT) as u8,
 	(data & MASK_8BIT) as u8)
 }
-fn pack_u8_u32(data: (u8, u8, u8, u8)) -> u32 {
+pub fn pack_u8_u32(data: (u8, u8, u8, u8)) -> u32 {
 	((data.0 as u32) << 24) |
 	((data.1 as u32) << 16) |
 	((data.2 as u32) << 8) |
 	(data.3 as u32)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/*
+	fill_oct as it was before the dedup table, finding shared nodes by scanning every node already at the depth
+	leaf octants match on colour and branch octants on index, the first match being the one reused
+	 */
+	fn fill_oct_linear<S: VolumeSource + ?Sized>(dag: &mut OctDag, source: &S, level_list: &mut Vec<Vec<u32>>, pos: IVec3, depth: u32, max_depth: u32) -> Octant {
+		let level_size = i32::pow(2, max_depth - depth);
+		let max_level_size = i32::pow(2, max_depth);
+		let mut octant = Octant::new();
+		let mut node = Node::new();
+
+		let mut max_vol = source.volume(pos.as_vec3(), max_level_size as f32);
+		max_vol.x /= level_size as f32;
+
+		if max_vol.x < -1.0 || (depth == max_depth && max_vol.x <= 1.0) {
+			octant = source.colour(pos, max_level_size);
+			octant.index = NULL_INDEX;
+			octant.normal = pack_normal_u32(max_vol.yzw(), 1.0);
+		} else if max_vol.x <= 1.0 {
+			let next_level_size = i32::pow(2, max_depth - depth - 1);
+			for (child, offset) in node.octants.iter_mut().zip(OCTANT_LIST) {
+				*child = fill_oct_linear(dag, source, level_list, pos + offset * IVec3::splat(next_level_size), depth + 1, max_depth);
+			}
+			octant = node.aggregate(depth);
+			let found = level_list[depth as usize].iter().find(|x| {
+				(0..OCTANT_COUNT).all(|i| {
+					let other = dag.nodes[**x as usize].octants[i];
+					if node.octants[i].index == NULL_INDEX {
+						other.index == NULL_INDEX && other.colour == node.octants[i].colour
+					} else {
+						other.index == node.octants[i].index
+					}
+				})
+			});
+			octant.index = match found {
+				Some(x) => *x,
+				None => {
+					dag.nodes.push(node);
+					level_list[depth as usize].push(dag.nodes.len() as u32 - 1);
+					dag.nodes.len() as u32 - 1
+				},
+			};
+		}
+		octant
+	}
+
+	fn new_test_linear(dag_type: TestDagType, max_depth: u32) -> OctDag {
+		let mut dag = OctDag{nodes: vec![Node::new()], max_depth};
+		let mut level_list = vec![Vec::new(); max_depth as usize];
+		let next_level_size = i32::pow(2, max_depth - 1);
+		for (i, offset) in OCTANT_LIST.iter().enumerate() {
+			dag.nodes[0].octants[i] = fill_oct_linear(&mut dag, dag_type.new(max_depth), &mut level_list, *offset * IVec3::splat(next_level_size), 1, max_depth);
+		}
+		dag
+	}
+
+	/*
+	a see through slab a few voxels thick, not lined up with the coarser octants, looked through straight on along each axis
+	the light let through with the lod cut off a few levels up should be close to what the leaves let through
+	it lets through a bit more the further up it is cut off, as more of each octant the slab is in is empty
+	 */
+	#[test]
+	fn slab_transmittance_converges() {
+		use crate::asset::voxel_grid::VoxelGrid;
+		let max_depth = 6;
+		let mut leaf = Octant::new();
+		leaf.colour = 0xFFFFFF80;
+		leaf.normal = pack_normal_u32(Vec3::Z, 0.0) | 0x80;
+		for axis in 0..3 {
+			let mut grid = VoxelGrid::new(max_depth);
+			let size = grid.size();
+			for a in 0..size {
+				for b in 0..size {
+					for depth in 37..40 {
+						let mut pos = [0; 3];
+						pos[axis] = depth;
+						pos[(axis + 1) % 3] = a;
+						pos[(axis + 2) % 3] = b;
+						grid.set(glam::UVec3::from_array(pos), leaf);
+					}
+				}
+			}
+			let dag = grid.build();
+			let mut origin = Vec3::splat(0.3);
+			origin[axis] = -100.0;
+			let direction = Vec3::AXES[axis];
+			let exact = dag.raycast_transmittance(origin, direction, 1000.0, max_depth).transmittance;
+			assert!(exact > 0.2 && exact < 0.8, "slab is {} see through", exact);
+			for k in 1..4 {
+				let coarse = dag.raycast_transmittance(origin, direction, 1000.0, max_depth - k).transmittance;
+				assert!((coarse - exact).abs() < 0.1, "axis {}, {} levels up: {} against {}", axis, k, coarse, exact);
+			}
+		}
+	}
+
+	fn check_normal(normal: Vec3) {
+		let decoded = unpack_u32_normal(pack_normal_u32(normal, 1.0)).truncate();
+		assert!((decoded.length() - 1.0).abs() < 1e-5, "{} decoded to {}", normal, decoded);
+		assert!(decoded.angle_between(normal) < 1e-3, "{} decoded to {}, {} rad off", normal, decoded, decoded.angle_between(normal));
+	}
+
+	#[test]
+	fn normal_round_trip() {
+		//spread evenly over the sphere
+		let count = 20000;
+		for i in 0..count {
+			let z = 1.0 - (2 * i + 1) as f32 / count as f32;
+			let angle = i as f32 * std::f32::consts::PI * (3.0 - 5f32.sqrt());
+			let radius = (1.0 - z * z).sqrt();
+			check_normal(Vec3::new(radius * angle.cos(), radius * angle.sin(), z));
+		}
+		for axis in [Vec3::X, Vec3::Y, Vec3::Z, Vec3::NEG_X, Vec3::NEG_Y, Vec3::NEG_Z] {
+			check_normal(axis);
+			assert_eq!(unpack_u32_normal(pack_normal_u32(axis, 1.0)).truncate(), axis);
+		}
+		//either side of the fold between the halves, and around -z where the corners of the square meet
+		for angle in (0..64).map(|i| i as f32 / 64.0 * std::f32::consts::TAU) {
+			for z in [0.0f32, 1e-6, -1e-6, -1e-3, -0.999999] {
+				let radius = (1.0 - z * z).sqrt();
+				check_normal(Vec3::new(radius * angle.cos(), radius * angle.sin(), z));
+			}
+		}
+		assert_eq!(pack_normal_u32(Vec3::ZERO, 0.0), 0);
+		assert_eq!(unpack_u32_normal(0), Vec4::new(0.0, 0.0, 1.0, 0.0));
+	}
+
+	#[test]
+	fn normal_keeps_density() {
+		for density in 0..=MASK_8BIT {
+			for normal in [Vec3::ZERO, Vec3::NEG_Z, Vec3::new(-0.3, 0.8, -0.5)] {
+				let packed = pack_normal_u32(normal, density as f32 / 255.0);
+				assert_eq!(packed & MASK_8BIT, density);
+				assert_eq!(unpack_u32_normal(packed).w, density as f32 / 255.0);
+			}
+		}
+	}
+
+	#[test]
+	fn dedup_table_matches_linear_scan() {
+		for max_depth in [4, 5, 7] {
+			assert!(OctDag::new_test(TestDagType::Box, max_depth).nodes == new_test_linear(TestDagType::Box, max_depth).nodes, "box at depth {}", max_depth);
+			assert!(OctDag::new_test(TestDagType::Pillar, max_depth).nodes == new_test_linear(TestDagType::Pillar, max_depth).nodes, "pillar at depth {}", max_depth);
+		}
+	}
+}
@@ -0,0 +1,161 @@
+use glam::Vec3;
+use crate::asset::oct_dag::{OctDag, Octant, OCTANT_COUNT, NULL_INDEX, SHADER_SIZE, unpack_u32_f32, unpack_u32_normal, absorption_distance};
+
+/*
+casting rays through the dag on the cpu, going through the octants the same way view_trace does
+positions are in build units, the root spanning -2^max_depth to 2^max_depth along each axis with its center at the origin
+the direction does not need to be normalized, distances are along it once it is
+octants past the given depth are not gone into, their aggregate being used as if they were leaves, like the lod cut off
+ */
+
+//from view_trace
+const MIN_TRANS: f32 = 0.001;
+
+#[derive(Clone, Copy, Debug)]
+pub struct RayHit {
+	pub position: Vec3,
+	pub octant: Octant,
+	pub depth: u32, //of the octant, the octants of the root being at 1
+	pub node: u32, //that the octant is in
+	pub distance: f32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RayTransmittance {
+	pub rgb: Vec3,
+	pub transmittance: f32,
+	pub hit: Option<RayHit>, //the octant that left too little light to go on, if any
+}
+
+struct Ray {
+	origin: Vec3,
+	direction: Vec3,
+	inverse: Vec3,
+	root_size: f32,
+	max_depth: u32,
+}
+
+impl OctDag {
+	//the first filled leaf along the ray
+	pub fn raycast(&self, origin: Vec3, direction: Vec3, max_dist: f32, max_depth: u32) -> Option<RayHit> {
+		let mut hit = None;
+		self.traverse(origin, direction, max_dist, max_depth, &mut |found| {
+			hit = Some(found.hit);
+			true
+		});
+		hit
+	}
+
+	/*
+	light let through the leaves along the ray, with the colours they add, the same as view_trace accumulates them
+	the distance through each leaf is scaled to the size the shader uses so that things look as dense as they do there
+	 */
+	pub fn raycast_transmittance(&self, origin: Vec3, direction: Vec3, max_dist: f32, max_depth: u32) -> RayTransmittance {
+		let scale = SHADER_SIZE / (1u32 << self.max_depth) as f32;
+		let mut result = RayTransmittance {
+			rgb: Vec3::ZERO,
+			transmittance: 1.0,
+			hit: None,
+		};
+		self.traverse(origin, direction, max_dist, max_depth, &mut |found| {
+			let rgba = unpack_u32_f32(found.hit.octant.colour);
+			let density = unpack_u32_normal(found.hit.octant.normal).w;
+			if density > 0.0 && rgba.w > 0.0 {
+				let len = found.octant_length * scale;
+				let absorbed = (1.0 - (-len / absorption_distance(rgba.w, density)).exp()) * result.transmittance;
+				result.transmittance -= absorbed;
+				result.rgb += rgba.truncate() * absorbed;
+			}
+			if result.transmittance < MIN_TRANS {
+				result.hit = Some(found.hit);
+				return true;
+			}
+			false
+		});
+		result
+	}
+
+	//calls back on every filled leaf along the ray in order until told to stop
+	fn traverse<F>(&self, origin: Vec3, direction: Vec3, max_dist: f32, max_depth: u32, visit: &mut F)
+	where F: FnMut(LeafCrossing) -> bool {
+		let direction = direction.normalize_or_zero();
+		if direction == Vec3::ZERO {
+			return;
+		}
+		let ray = Ray {
+			origin,
+			direction,
+			inverse: Vec3::ONE / direction,
+			root_size: (1u32 << self.max_depth) as f32,
+			max_depth: max_depth.max(1),
+		};
+		let size = ray.root_size;
+		let low = (Vec3::splat(-size) - origin) * ray.inverse;
+		let high = (Vec3::splat(size) - origin) * ray.inverse;
+		let enter = low.min(high).max_element().max(0.0);
+		let exit = low.max(high).min_element().min(max_dist);
+		if enter < exit {
+			self.traverse_node(&ray, 0, Vec3::ZERO, (enter, exit), 1, visit);
+		}
+	}
+
+	//steps through the octants of the node from enter to exit, crossing one of the center planes each time
+	fn traverse_node<F>(&self, ray: &Ray, node: u32, center: Vec3, (enter, exit): (f32, f32), depth: u32, visit: &mut F) -> bool
+	where F: FnMut(LeafCrossing) -> bool {
+		let half_size = ray.root_size / (1u32 << (depth - 1)) as f32;
+		let to_center = (center - ray.origin) * ray.inverse;
+		let mut octant_index = calculate_octant(ray.origin + ray.direction * enter, center, ray.direction);
+		let mut start = enter;
+		loop {
+			let crossings = Vec3::select(to_center.cmpgt(Vec3::splat(start)), to_center, Vec3::splat(f32::INFINITY));
+			let end = crossings.min_element().min(exit);
+
+			let octant = self.nodes[node as usize].octants[octant_index];
+			if octant != Octant::new() {
+				let child_center = center + (octant_offset(octant_index) * 2.0 - Vec3::ONE) * (half_size / 2.0);
+				let stop = if octant.index == NULL_INDEX || depth >= ray.max_depth {
+					visit(LeafCrossing {
+						hit: RayHit {
+							position: ray.origin + ray.direction * start,
+							octant,
+							depth,
+							node,
+							distance: start,
+						},
+						octant_length: end - start,
+					})
+				} else {
+					self.traverse_node(ray, octant.index, child_center, (start, end), depth + 1, visit)
+				};
+				if stop {
+					return true;
+				}
+			}
+
+			if end >= exit {
+				return false;
+			}
+			for axis in 0..3 {
+				if crossings[axis] == end {
+					octant_index ^= 1 << axis;
+				}
+			}
+			start = end;
+		}
+	}
+}
+
+struct LeafCrossing {
+	hit: RayHit,
+	octant_length: f32,
+}
+
+//the same as calculate_octant in view_trace, rays on a plane going to the side they are heading to
+fn calculate_octant(position: Vec3, center: Vec3, direction: Vec3) -> usize {
+	let positive = position.cmpgt(center) | (position.cmpeq(center) & direction.cmpgt(Vec3::ZERO));
+	positive.bitmask() as usize & (OCTANT_COUNT - 1)
+}
+
+fn octant_offset(i: usize) -> Vec3 {
+	Vec3::new((i & 1) as f32, ((i >> 1) & 1) as f32, ((i >> 2) & 1) as f32)
+}
@@ -0,0 +1,193 @@
+use glam::{Vec2, Vec3, Vec4, IVec3, Quat};
+use crate::asset::oct_dag::{Octant, VolumeSource, pack_f32_u32, tetrahedron_gradient, true_distance_volume};
+
+/*
+signed distance shapes that can be combined and moved around, then built into a dag as a VolumeSource
+distances are in build units, the root spanning -2^max_depth to 2^max_depth, negative being inside
+normals are the gradient of the distance, found by sampling around the point, so any combination of shapes gets them
+every shape is white unless painted, and combinations keep the colour of the shape the point is taken from
+ */
+#[derive(Clone, Debug)]
+pub enum Sdf {
+	Sphere { radius: f32 },
+	Box { half_extents: Vec3 },
+	RoundedBox { half_extents: Vec3, radius: f32 },
+	Torus { major: f32, minor: f32 }, //lying flat, around the y axis
+	Capsule { a: Vec3, b: Vec3, radius: f32 },
+	Cylinder { radius: f32, half_height: f32 }, //along the y axis
+	Cone { radius: f32, half_height: f32 }, //along the y axis with the point at the top
+	Plane { normal: Vec3, offset: f32 }, //everything below the plane along the normal is inside
+
+	Union(Box<Sdf>, Box<Sdf>),
+	Subtract(Box<Sdf>, Box<Sdf>), //the first without the second
+	Intersect(Box<Sdf>, Box<Sdf>),
+	SmoothUnion(Box<Sdf>, Box<Sdf>, f32), //blended over about the given distance, colours too
+
+	Translate(Box<Sdf>, Vec3),
+	Rotate(Box<Sdf>, Quat),
+	Scale(Box<Sdf>, f32),
+	Repeat(Box<Sdf>, Vec3), //tiled with the given period along each axis, 0 for axes not to repeat along
+	Paint(Box<Sdf>, Vec4), //rgba
+}
+
+const DEFAULT_COLOUR: Vec4 = Vec4::ONE;
+
+impl Sdf {
+	pub fn sphere(radius: f32) -> Self {
+		Sdf::Sphere { radius }
+	}
+	pub fn cuboid(half_extents: Vec3) -> Self {
+		Sdf::Box { half_extents }
+	}
+	pub fn rounded_box(half_extents: Vec3, radius: f32) -> Self {
+		Sdf::RoundedBox { half_extents, radius }
+	}
+	pub fn torus(major: f32, minor: f32) -> Self {
+		Sdf::Torus { major, minor }
+	}
+	pub fn capsule(a: Vec3, b: Vec3, radius: f32) -> Self {
+		Sdf::Capsule { a, b, radius }
+	}
+	pub fn cylinder(radius: f32, half_height: f32) -> Self {
+		Sdf::Cylinder { radius, half_height }
+	}
+	pub fn cone(radius: f32, half_height: f32) -> Self {
+		Sdf::Cone { radius, half_height }
+	}
+	pub fn plane(normal: Vec3, offset: f32) -> Self {
+		Sdf::Plane { normal: normal.normalize(), offset }
+	}
+
+	pub fn union(self, other: Sdf) -> Self {
+		Sdf::Union(Box::new(self), Box::new(other))
+	}
+	pub fn subtract(self, other: Sdf) -> Self {
+		Sdf::Subtract(Box::new(self), Box::new(other))
+	}
+	pub fn intersect(self, other: Sdf) -> Self {
+		Sdf::Intersect(Box::new(self), Box::new(other))
+	}
+	pub fn smooth_union(self, other: Sdf, blend: f32) -> Self {
+		Sdf::SmoothUnion(Box::new(self), Box::new(other), blend)
+	}
+	pub fn translate(self, offset: Vec3) -> Self {
+		Sdf::Translate(Box::new(self), offset)
+	}
+	pub fn rotate(self, rotation: Quat) -> Self {
+		Sdf::Rotate(Box::new(self), rotation)
+	}
+	pub fn scale(self, scale: f32) -> Self {
+		Sdf::Scale(Box::new(self), scale)
+	}
+	pub fn repeat(self, period: Vec3) -> Self {
+		Sdf::Repeat(Box::new(self), period)
+	}
+	pub fn paint(self, colour: Vec4) -> Self {
+		Sdf::Paint(Box::new(self), colour)
+	}
+
+	pub fn distance(&self, pos: Vec3) -> f32 {
+		self.sample(pos).0
+	}
+	//pointing out of the shape, not normalized
+	pub fn gradient(&self, pos: Vec3) -> Vec3 {
+		tetrahedron_gradient(|pos| self.distance(pos), pos)
+	}
+
+	//the distance and the colour of the shape it is to
+	pub fn sample(&self, pos: Vec3) -> (f32, Vec4) {
+		match self {
+			Sdf::Sphere { radius } => (pos.length() - radius, DEFAULT_COLOUR),
+			Sdf::Box { half_extents } => (box_distance(pos, *half_extents), DEFAULT_COLOUR),
+			Sdf::RoundedBox { half_extents, radius } => (box_distance(pos, *half_extents - Vec3::splat(*radius)) - radius, DEFAULT_COLOUR),
+			Sdf::Torus { major, minor } => {
+				let ring = Vec2::new(Vec2::new(pos.x, pos.z).length() - major, pos.y);
+				(ring.length() - minor, DEFAULT_COLOUR)
+			},
+			Sdf::Capsule { a, b, radius } => {
+				let (along, axis) = (pos - *a, *b - *a);
+				let h = (along.dot(axis) / axis.length_squared()).clamp(0.0, 1.0);
+				((along - axis * h).length() - radius, DEFAULT_COLOUR)
+			},
+			Sdf::Cylinder { radius, half_height } => {
+				let d = Vec2::new(Vec2::new(pos.x, pos.z).length(), pos.y).abs() - Vec2::new(*radius, *half_height);
+				(d.max_element().min(0.0) + d.max(Vec2::ZERO).length(), DEFAULT_COLOUR)
+			},
+			Sdf::Cone { radius, half_height } => (cone_distance(pos, *radius, *half_height), DEFAULT_COLOUR),
+			Sdf::Plane { normal, offset } => (pos.dot(*normal) - offset, DEFAULT_COLOUR),
+
+			Sdf::Union(a, b) => {
+				let (a, b) = (a.sample(pos), b.sample(pos));
+				if a.0 <= b.0 { a } else { b }
+			},
+			Sdf::Subtract(a, b) => {
+				let (a, b) = (a.sample(pos), b.sample(pos));
+				(a.0.max(-b.0), a.1)
+			},
+			Sdf::Intersect(a, b) => {
+				let (a, b) = (a.sample(pos), b.sample(pos));
+				if a.0 >= b.0 { a } else { b }
+			},
+			Sdf::SmoothUnion(a, b, blend) => {
+				let (a, b) = (a.sample(pos), b.sample(pos));
+				let h = (0.5 + 0.5 * (b.0 - a.0) / blend.max(f32::EPSILON)).clamp(0.0, 1.0);
+				(b.0 + (a.0 - b.0) * h - blend * h * (1.0 - h), b.1.lerp(a.1, h))
+			},
+
+			Sdf::Translate(sdf, offset) => sdf.sample(pos - *offset),
+			Sdf::Rotate(sdf, rotation) => sdf.sample(rotation.inverse() * pos),
+			Sdf::Scale(sdf, scale) => {
+				let (distance, colour) = sdf.sample(pos / *scale);
+				(distance * scale, colour)
+			},
+			Sdf::Repeat(sdf, period) => {
+				let cell = Vec3::select(period.cmpgt(Vec3::ZERO), *period * (pos / *period).round(), Vec3::ZERO);
+				sdf.sample(pos - cell)
+			},
+			Sdf::Paint(sdf, colour) => (sdf.sample(pos).0, *colour),
+		}
+	}
+}
+
+impl VolumeSource for Sdf {
+	fn volume(&self, pos: Vec3, _max_level_size: f32) -> Vec4 {
+		true_distance_volume(self.distance(pos), self.gradient(pos))
+	}
+	fn colour(&self, pos: IVec3, _max_level_size: i32) -> Octant {
+		let mut octant = Octant::new();
+		octant.colour = pack_f32_u32(self.sample(pos.as_vec3()).1);
+		octant
+	}
+	fn distance_is_bound(&self) -> bool {
+		true
+	}
+}
+
+fn box_distance(pos: Vec3, half_extents: Vec3) -> f32 {
+	let q = pos.abs() - half_extents;
+	q.max(Vec3::ZERO).length() + q.max_element().min(0.0)
+}
+//a capped cone with a radius of 0 at the top, working in the plane through the axis and the point
+fn cone_distance(pos: Vec3, radius: f32, half_height: f32) -> f32 {
+	let q = Vec2::new(Vec2::new(pos.x, pos.z).length(), pos.y);
+	let top = Vec2::new(0.0, half_height);
+	let side = Vec2::new(-radius, 2.0 * half_height);
+	let to_cap = Vec2::new(q.x - q.x.min(if q.y < 0.0 { radius } else { 0.0 }), q.y.abs() - half_height);
+	let to_side = q - top + side * ((top - q).dot(side) / side.length_squared()).clamp(0.0, 1.0);
+	let sign = if to_side.x < 0.0 && to_cap.y < 0.0 { -1.0 } else { 1.0 };
+	sign * to_cap.length_squared().min(to_side.length_squared()).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::asset::{oct_dag::OctDag, validate::DagViolation};
+
+	//the shrunk distances send fill_oct down into octants that turn out to be empty, none of which should be kept
+	#[test]
+	fn no_empty_nodes() {
+		let shape = Sdf::sphere(60.0).subtract(Sdf::sphere(30.0).translate(Vec3::new(40.0, 20.0, 0.0)));
+		let violations = OctDag::new_from_source(7, &shape).validate();
+		assert!(!violations.iter().any(|violation| matches!(violation, DagViolation::EmptyBranch { .. })), "{:?}", violations);
+	}
+}
@@ -0,0 +1,180 @@
+use std::{fmt, mem::size_of, collections::HashMap};
+use crate::asset::oct_dag::{OctDag, Octant, OCTANT_COUNT, NULL_INDEX};
+
+/*
+the tree structure and the voxel data kept apart, so that subtrees with the same shape are shared even when their colours differ
+attributes are stored in the order a depth first walk of the expanded tree visits the octants, empty octants having none
+each octant holds how many attributes come before it in its node, so that while descending
+	the attribute of an octant is the attribute index of its node plus its offset
+	and the attribute index of a child node is one past the attribute of the octant pointing to it
+ */
+pub const EMPTY_OFFSET: u32 = 0xFFFFFFFF;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct TopologyOctant {
+	pub index: u32, //index of the next node
+	pub offset: u32, //attributes before this octant within the node, or EMPTY_OFFSET
+}
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct TopologyNode {
+	pub octants: [TopologyOctant; OCTANT_COUNT],
+}
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Attribute {
+	pub colour: u32,
+	pub normal: u32,
+	pub extra: u32,
+}
+pub struct SplitDag {
+	pub topology: Vec<TopologyNode>,
+	pub attributes: Vec<Attribute>,
+	pub max_depth: u32,
+}
+
+#[derive(Debug)]
+pub enum SplitDagError {
+	TooManyAttributes(u64), //the expanded tree has more octants than a u32 attribute index can reach
+}
+
+impl SplitDag {
+	/*
+	topology nodes are shared between all levels, since nothing in them depends on depth
+	the attributes are not shared at all, so the stream grows with the expanded tree rather than the dag
+	 */
+	pub fn from_dag(dag: &OctDag) -> Result<Self, SplitDagError> {
+		let mut split = SplitDag {
+			topology: vec![TopologyNode::new()],
+			attributes: Vec::new(),
+			max_depth: dag.max_depth,
+		};
+		let mut converted: HashMap<u32, (u32, u64)> = HashMap::new();
+		let mut dedup: HashMap<TopologyNode, u32> = HashMap::new();
+		let (root, count) = split.convert_topology(dag, 0, &mut converted, &mut dedup)?;
+		if count >= EMPTY_OFFSET as u64 {
+			return Err(SplitDagError::TooManyAttributes(count));
+		}
+		split.topology[0] = root;
+		split.attributes.reserve(count as usize);
+		split.push_attributes(dag);
+		Ok(split)
+	}
+
+	//returns the topology node for a dag node along with how many attributes its subtree has
+	fn convert_topology(&mut self, dag: &OctDag, index: u32,
+		converted: &mut HashMap<u32, (u32, u64)>,
+		dedup: &mut HashMap<TopologyNode, u32>) -> Result<(TopologyNode, u64), SplitDagError> {
+
+		let mut node = TopologyNode::new();
+		let mut count: u64 = 0;
+		for i in 0..OCTANT_COUNT {
+			let octant = dag.nodes[index as usize].octants[i];
+			if octant == Octant::new() {
+				continue;
+			}
+			node.octants[i].offset = u32::try_from(count).map_err(|_| SplitDagError::TooManyAttributes(count))?;
+			count += 1;
+			if octant.index != NULL_INDEX {
+				let (child_index, child_count) = match converted.get(&octant.index) {
+					Some(child) => *child,
+					None => {
+						let (child, child_count) = self.convert_topology(dag, octant.index, converted, dedup)?;
+						let child_index = *dedup.entry(child).or_insert_with(|| {
+							self.topology.push(child);
+							self.topology.len() as u32 - 1
+						});
+						converted.insert(octant.index, (child_index, child_count));
+						(child_index, child_count)
+					},
+				};
+				node.octants[i].index = child_index;
+				count += child_count;
+			}
+		}
+		Ok((node, count))
+	}
+
+	//the walk over the expanded tree is kept on its own stack, as shared nodes make it as deep and long as the tree is
+	fn push_attributes(&mut self, dag: &OctDag) {
+		let mut stack = vec![(0u32, 0usize)];
+		while let Some((index, next)) = stack.last_mut() {
+			if *next == OCTANT_COUNT {
+				stack.pop();
+				continue;
+			}
+			let octant = dag.nodes[*index as usize].octants[*next];
+			*next += 1;
+			if octant == Octant::new() {
+				continue;
+			}
+			self.attributes.push(Attribute { colour: octant.colour, normal: octant.normal, extra: octant.extra });
+			if octant.index != NULL_INDEX {
+				stack.push((octant.index, 0));
+			}
+		}
+	}
+
+	//the octant as it would be in the original dag, given the attribute index of the node it is in
+	pub fn fetch_octant(&self, node_index: u32, attribute_base: u32, octant_index: usize) -> (Octant, u32) {
+		let topology_octant = self.topology[node_index as usize].octants[octant_index];
+		let mut octant = Octant::new();
+		if topology_octant.offset == EMPTY_OFFSET {
+			return (octant, EMPTY_OFFSET);
+		}
+		let attribute_index = attribute_base + topology_octant.offset;
+		let attribute = self.attributes[attribute_index as usize];
+		octant.index = topology_octant.index;
+		octant.colour = attribute.colour;
+		octant.normal = attribute.normal;
+		octant.extra = attribute.extra;
+		(octant, attribute_index)
+	}
+
+	pub fn print_size(&self) {
+		println!("Topology node count: {}, attribute count: {}, Size in Mb: {}",
+			self.topology.len(), self.attributes.len(),
+			(self.topology.len() * size_of::<TopologyNode>() + self.attributes.len() * size_of::<Attribute>()) as f32 / 1000000.0)
+	}
+}
+
+impl TopologyNode {
+	pub fn new() -> Self {
+		Self { octants: [TopologyOctant { index: NULL_INDEX, offset: EMPTY_OFFSET }; OCTANT_COUNT] }
+	}
+}
+
+impl fmt::Display for SplitDagError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			SplitDagError::TooManyAttributes(count) => write!(f, "{} attributes is too many for the split layout", count),
+		}
+	}
+}
+impl std::error::Error for SplitDagError {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::asset::oct_dag::TestDagType;
+
+	fn compare(dag: &OctDag, split: &SplitDag, index: u32, node_index: u32, attribute_base: u32) {
+		for i in 0..OCTANT_COUNT {
+			let expected = dag.nodes[index as usize].octants[i];
+			let (octant, attribute_index) = split.fetch_octant(node_index, attribute_base, i);
+			assert_eq!((octant.colour, octant.normal, octant.extra), (expected.colour, expected.normal, expected.extra));
+			assert_eq!(octant.index == NULL_INDEX, expected.index == NULL_INDEX);
+			if expected.index != NULL_INDEX {
+				compare(dag, split, expected.index, octant.index, attribute_index + 1);
+			}
+		}
+	}
+
+	#[test]
+	fn split_reads_back_as_dag() {
+		let dag = OctDag::new_test(TestDagType::Pillar, 6);
+		let split = SplitDag::from_dag(&dag).unwrap();
+		compare(&dag, &split, 0, 0, 0);
+	}
+}
@@ -0,0 +1,198 @@
+use std::{mem::size_of, collections::{HashMap, HashSet}};
+use crate::asset::{oct_dag::{OctDag, Node, Octant, OCTANT_COUNT, NULL_INDEX}, validate::DagViolation};
+
+/*
+counts over the part of the dag reachable from the root, for keeping track of how big assets are and where the size goes
+levels are by depth, the root alone being level 0, with a node that can be reached at several depths counted at each of them
+instances are how many nodes a plain octree would have at that level, which shared nodes stand in for
+the totals over the whole dag count each reachable node once
+the dag is validated first, as a bad index would panic and a cycle would never run out of levels, so a loaded file can be given as is
+ */
+pub struct LevelStats {
+	pub nodes: usize,
+	pub instances: f64, //as a float since it can be far too big for any int at deep levels
+	pub references: usize, //octants in the level above pointing into this one
+	pub leaf_octants: usize,
+	pub inner_octants: usize,
+	pub empty_octants: usize,
+}
+
+pub struct DagStats {
+	pub max_depth: u32,
+	pub pool_nodes: usize, //including the ones the root can no longer reach
+	pub reachable_nodes: usize,
+	pub levels: Vec<LevelStats>,
+	pub leaf_octants: usize,
+	pub inner_octants: usize,
+	pub empty_octants: usize,
+	pub node_bytes: usize, //the reachable nodes in the node layout
+	pub index_bytes: usize, //the index of every reachable octant, which is per octant however many share attributes
+	pub attribute_bytes: [(&'static str, usize); 3], //each distinct colour, normal and extra once, the least any layout could store them in
+}
+
+impl OctDag {
+	pub fn stats(&self) -> Result<DagStats, Vec<DagViolation>> {
+		let violations: Vec<DagViolation> = self.validate().into_iter().filter(|violation| violation.is_fatal()).collect();
+		if !violations.is_empty() {
+			return Err(violations);
+		}
+		let mut level_stats: Vec<LevelStats> = Vec::new();
+		//the nodes at the current depth in the order they are first found, with how many times each is instanced
+		let mut level: Vec<(u32, f64)> = vec![(0, 1.0)];
+		while !level.is_empty() {
+			let mut stats = LevelStats {
+				nodes: level.len(),
+				instances: level.iter().map(|(_, instances)| instances).sum(),
+				references: level_stats.last().map_or(0, |previous| previous.inner_octants),
+				leaf_octants: 0,
+				inner_octants: 0,
+				empty_octants: 0,
+			};
+			let mut next: Vec<(u32, f64)> = Vec::new();
+			let mut positions: HashMap<u32, usize> = HashMap::new();
+			for (index, instances) in level {
+				let (leaf, inner, empty) = self.nodes[index as usize].octant_counts();
+				stats.leaf_octants += leaf;
+				stats.inner_octants += inner;
+				stats.empty_octants += empty;
+				for octant in self.nodes[index as usize].octants {
+					if octant.index != NULL_INDEX {
+						let position = *positions.entry(octant.index).or_insert_with(|| {
+							next.push((octant.index, 0.0));
+							next.len() - 1
+						});
+						next[position].1 += instances;
+					}
+				}
+			}
+			level_stats.push(stats);
+			level = next;
+		}
+
+		let reachable: Vec<u32> = self.levels().into_iter().flatten().collect();
+		let (mut leaf_octants, mut inner_octants, mut empty_octants) = (0, 0, 0);
+		let (mut colours, mut normals, mut extras) = (HashSet::new(), HashSet::new(), HashSet::new());
+		for index in reachable.iter() {
+			let (leaf, inner, empty) = self.nodes[*index as usize].octant_counts();
+			leaf_octants += leaf;
+			inner_octants += inner;
+			empty_octants += empty;
+			for octant in self.nodes[*index as usize].octants.iter().filter(|octant| **octant != Octant::new()) {
+				colours.insert(octant.colour);
+				normals.insert(octant.normal);
+				extras.insert(octant.extra);
+			}
+		}
+		let value_bytes = size_of::<u32>();
+		Ok(DagStats {
+			max_depth: self.max_depth,
+			pool_nodes: self.nodes.len(),
+			reachable_nodes: reachable.len(),
+			levels: level_stats,
+			leaf_octants,
+			inner_octants,
+			empty_octants,
+			node_bytes: reachable.len() * size_of::<Node>(),
+			index_bytes: reachable.len() * OCTANT_COUNT * value_bytes,
+			attribute_bytes: [("colour", colours.len() * value_bytes), ("normal", normals.len() * value_bytes), ("extra", extras.len() * value_bytes)],
+		})
+	}
+}
+
+impl Node {
+	//leaf, inner and empty
+	fn octant_counts(&self) -> (usize, usize, usize) {
+		let empty = self.octants.iter().filter(|octant| **octant == Octant::new()).count();
+		let inner = self.octants.iter().filter(|octant| octant.index != NULL_INDEX).count();
+		(OCTANT_COUNT - empty - inner, inner, empty)
+	}
+}
+
+impl LevelStats {
+	//instances per node, 1 meaning nothing at this level is shared
+	pub fn dedup_ratio(&self) -> f64 {
+		if self.nodes == 0 { 0.0 } else { self.instances / self.nodes as f64 }
+	}
+}
+
+impl DagStats {
+	pub fn octants(&self) -> usize {
+		self.leaf_octants + self.inner_octants + self.empty_octants
+	}
+	pub fn empty_fraction(&self) -> f32 {
+		if self.octants() == 0 { 0.0 } else { self.empty_octants as f32 / self.octants() as f32 }
+	}
+	//how many octants point to each node on average, the root having none
+	pub fn average_in_degree(&self) -> f32 {
+		if self.reachable_nodes < 2 { 0.0 } else { self.inner_octants as f32 / (self.reachable_nodes - 1) as f32 }
+	}
+
+	pub fn report(&self) -> String {
+		let mut report = String::new();
+		for (depth, level) in self.levels.iter().enumerate() {
+			report += &format!("Depth: {}, nodes: {}, instances: {:.0}, dedup ratio: {:.2}, leaf: {}, inner: {}, empty: {}\n",
+				depth, level.nodes, level.instances, level.dedup_ratio(), level.leaf_octants, level.inner_octants, level.empty_octants);
+		}
+		report += &format!("Nodes: {} reachable of {}, max depth: {}\n", self.reachable_nodes, self.pool_nodes, self.max_depth);
+		report += &format!("Octants: {} leaf, {} inner, {:.2}% empty, average in degree: {:.2}\n",
+			self.leaf_octants, self.inner_octants, self.empty_fraction() * 100.0, self.average_in_degree());
+		report += &format!("Node layout: {} Mb, of which indices: {} Mb\n", self.node_bytes as f32 / 1000000.0, self.index_bytes as f32 / 1000000.0);
+		report += "Distinct attributes,";
+		for (name, bytes) in self.attribute_bytes {
+			report += &format!(" {}: {} Mb", name, bytes as f32 / 1000000.0);
+		}
+		report
+	}
+	pub fn print_report(&self) {
+		println!("{}", self.report());
+	}
+
+	//numbers only, so nothing needs escaping apart from the attribute names which are fixed
+	pub fn to_json(&self) -> String {
+		let levels: Vec<String> = self.levels.iter().map(|level| format!(
+			"{{\"nodes\":{},\"instances\":{},\"references\":{},\"dedup_ratio\":{},\"leaf_octants\":{},\"inner_octants\":{},\"empty_octants\":{}}}",
+			level.nodes, level.instances, level.references, level.dedup_ratio(), level.leaf_octants, level.inner_octants, level.empty_octants)).collect();
+		let attributes: Vec<String> = self.attribute_bytes.iter().map(|(name, bytes)| format!("\"{}\":{}", name, bytes)).collect();
+		format!("{{\"max_depth\":{},\"pool_nodes\":{},\"reachable_nodes\":{},\"leaf_octants\":{},\"inner_octants\":{},\"empty_octants\":{},\"empty_fraction\":{},\"average_in_degree\":{},\"node_bytes\":{},\"index_bytes\":{},\"attribute_bytes\":{{{}}},\"levels\":[{}]}}",
+			self.max_depth, self.pool_nodes, self.reachable_nodes, self.leaf_octants, self.inner_octants, self.empty_octants,
+			self.empty_fraction(), self.average_in_degree(), self.node_bytes, self.index_bytes, attributes.join(","), levels.join(","))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::asset::oct_dag::TestDagType;
+
+	//the node at index 2 is reached from the root and from the node below the root, so it is at both depths
+	#[test]
+	fn instances_per_depth() {
+		let mut leaf = Octant::new();
+		leaf.colour = 0xFF0000FF;
+		leaf.normal = 0x800800FF;
+		let mut dag = OctDag{nodes: vec![Node::new(); 3], max_depth: 3};
+		dag.nodes[2].octants[0] = leaf;
+		dag.nodes[2].octants[7] = leaf;
+		dag.nodes[1].octants = [Octant{index: 2, ..leaf}; OCTANT_COUNT];
+		dag.nodes[0].octants[0] = Octant{index: 1, ..leaf};
+		dag.nodes[0].octants[1] = Octant{index: 2, ..leaf};
+		dag.nodes[0].octants[2] = Octant{index: 2, ..leaf};
+
+		let stats = dag.stats().unwrap();
+		let levels: Vec<(usize, f64)> = stats.levels.iter().map(|level| (level.nodes, level.instances)).collect();
+		assert_eq!(levels, vec![(1, 1.0), (2, 3.0), (1, 8.0)]);
+		assert_eq!(stats.reachable_nodes, 3);
+		assert_eq!((stats.leaf_octants, stats.inner_octants, stats.empty_octants), (2, 11, 11));
+		assert_eq!(stats.index_bytes, 96);
+		assert_eq!(stats.attribute_bytes, [("colour", 4), ("normal", 4), ("extra", 4)]);
+	}
+
+	#[test]
+	fn cycle_is_refused() {
+		let mut dag = OctDag::new_test(TestDagType::Box, 4);
+		let last = dag.nodes.len() - 1;
+		dag.nodes[last].octants[0].index = 0;
+		let violations = dag.stats().err().unwrap();
+		assert!(violations.iter().any(|violation| matches!(violation, DagViolation::Cycle { .. })), "{:?}", violations);
+	}
+}
@@ -0,0 +1,171 @@
+use std::{fmt, mem::size_of, hash::BuildHasherDefault, collections::HashMap};
+use glam::Vec3;
+use crate::asset::oct_dag::{OctDag, Node, Octant, NodeHasher, OCTANT_COUNT, NULL_INDEX, MASK_8BIT, pack_normal_u32, unpack_u32_normal};
+
+/*
+a dag where nodes that are reflections of each other are shared
+the top three bits of a child index say which axes the child is mirrored along, x being the lowest, the same as octant indices
+mirroring along an axis swaps the octants on either side of it and flips the normals, so a mirrored node is read as
+	octant i of the mirrored node is octant i ^ mirror of the stored node
+	with the mirror of its child being the child's own mirror ^ mirror
+ */
+pub const MIRROR_SHIFT: u32 = 29;
+//the last index is never used, as with all three mirror bits set it would be NULL_INDEX
+pub const INDEX_MASK: u32 = (1 << MIRROR_SHIFT) - 1;
+
+pub struct SymmetricDag {
+	pub nodes: Vec<Node>,
+	pub max_depth: u32,
+}
+
+#[derive(Debug)]
+pub enum SymmetricDagError {
+	TooManyNodes(u32), //the index of a node that does not fit below the mirror bits
+}
+
+impl SymmetricDag {
+	/*
+	each node is stored as whichever of its 8 reflections sorts lowest, so that all of them find the same one
+	unlike the plain dag, nodes are shared between levels too
+	the root is kept as it is at index 0
+	 */
+	pub fn from_dag(dag: &OctDag) -> Result<Self, SymmetricDagError> {
+		let mut symmetric = SymmetricDag {
+			nodes: vec![Node::new()],
+			max_depth: dag.max_depth,
+		};
+		let mut converted: Vec<Option<u32>> = vec![None; dag.nodes.len()];
+		let mut dedup: HashMap<Node, u32, BuildHasherDefault<NodeHasher>> = HashMap::default();
+		symmetric.nodes[0] = symmetric.convert_children(dag, 0, &mut converted, &mut dedup)?;
+		Ok(symmetric)
+	}
+
+	//the node with its children replaced by their shared reflections
+	fn convert_children(&mut self, dag: &OctDag, index: u32, converted: &mut Vec<Option<u32>>,
+		dedup: &mut HashMap<Node, u32, BuildHasherDefault<NodeHasher>>) -> Result<Node, SymmetricDagError> {
+
+		let mut node = dag.nodes[index as usize];
+		for octant in node.octants.iter_mut() {
+			if octant.index == NULL_INDEX {
+				continue;
+			}
+			octant.index = match converted[octant.index as usize] {
+				Some(reference) => reference,
+				None => {
+					let child = self.convert_children(dag, octant.index, converted, dedup)?;
+					let (canonical, mirror) = child.canonical_reflection();
+					let child_index = *dedup.entry(canonical).or_insert_with(|| {
+						self.nodes.push(canonical);
+						self.nodes.len() as u32 - 1
+					});
+					let reference = child_reference(child_index, mirror)?;
+					converted[octant.index as usize] = Some(reference);
+					reference
+				},
+			};
+		}
+		Ok(node)
+	}
+
+	//the octant as it would be in the original dag, along with the mirror to read its child with
+	pub fn fetch_octant(&self, node_index: u32, mirror: u32, octant_index: usize) -> (Octant, u32) {
+		let mut octant = self.nodes[node_index as usize].octants[octant_index ^ mirror as usize].mirrored(mirror);
+		let mut child_mirror = 0;
+		if octant.index != NULL_INDEX {
+			child_mirror = octant.index >> MIRROR_SHIFT;
+			octant.index &= INDEX_MASK;
+		}
+		(octant, child_mirror)
+	}
+
+	pub fn print_size(&self) {
+		println!("Symmetric node count: {}, Size in Mb: {}", self.nodes.len(), (self.nodes.len() * size_of::<Node>()) as f32 / 1000000.0)
+	}
+}
+
+//the child index with its mirror in the top bits
+fn child_reference(child_index: u32, mirror: u32) -> Result<u32, SymmetricDagError> {
+	if child_index >= INDEX_MASK {
+		return Err(SymmetricDagError::TooManyNodes(child_index));
+	}
+	Ok(child_index | mirror << MIRROR_SHIFT)
+}
+
+impl Node {
+	pub fn mirrored(&self, mirror: u32) -> Node {
+		let mut node = Node::new();
+		for i in 0..OCTANT_COUNT {
+			node.octants[i ^ mirror as usize] = self.octants[i].mirrored(mirror);
+		}
+		node
+	}
+	/*
+	the lowest of the reflections of the node, and the mirror that turns it back into this node
+	reflections that can not be turned back exactly, such as when a flipped normal can not be packed, are skipped
+	 */
+	pub fn canonical_reflection(&self) -> (Node, u32) {
+		let key = |node: &Node| node.octants.map(|octant| (octant.index, octant.colour, octant.normal, octant.extra));
+		(0..OCTANT_COUNT as u32)
+			.map(|mirror| (self.mirrored(mirror), mirror))
+			.filter(|(node, mirror)| node.mirrored(*mirror) == *self)
+			.min_by_key(|(node, _)| key(node))
+			.unwrap()
+	}
+}
+
+impl Octant {
+	//the normal flipped along the mirrored axes, and the mirror carried on to the child
+	//empty octants stay empty, as the zero normal does not come back as zero once flipped
+	pub fn mirrored(&self, mirror: u32) -> Octant {
+		let mut octant = *self;
+		if mirror == 0 || octant == Octant::new() {
+			return octant;
+		}
+		if octant.index != NULL_INDEX {
+			octant.index ^= mirror << MIRROR_SHIFT;
+		}
+		let normal = unpack_u32_normal(octant.normal);
+		let flip = |axis: u32, value: f32| if mirror & axis != 0 { -value } else { value };
+		//the density is kept as it was rather than going through a float
+		octant.normal = pack_normal_u32(Vec3::new(flip(1, normal.x), flip(2, normal.y), flip(4, normal.z)), 0.0) | (octant.normal & MASK_8BIT);
+		octant
+	}
+}
+
+impl fmt::Display for SymmetricDagError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			SymmetricDagError::TooManyNodes(index) => write!(f, "node {} does not fit below the mirror bits, the most is {}", index, INDEX_MASK),
+		}
+	}
+}
+impl std::error::Error for SymmetricDagError {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn last_index_is_reserved() {
+		assert_eq!(child_reference(INDEX_MASK - 1, 7).unwrap(), NULL_INDEX - 1);
+		assert!(child_reference(INDEX_MASK, 7).is_err());
+		assert!(child_reference(INDEX_MASK, 0).is_err());
+	}
+
+	//a node and its reflections have to come to the same canonical node for them to be shared
+	#[test]
+	fn reflections_share_empty_octants() {
+		for mirror in 0..OCTANT_COUNT as u32 {
+			assert!(Octant::new().mirrored(mirror) == Octant::new());
+		}
+		let mut node = Node::new();
+		node.octants[0] = Octant { colour: 0xFF0000FF, normal: pack_normal_u32(Vec3::new(1.0, 2.0, 3.0).normalize(), 1.0), ..Octant::new() };
+		node.octants[3] = Octant { colour: 0x00FF00FF, normal: pack_normal_u32(Vec3::new(-3.0, 1.0, 0.5).normalize(), 0.5), ..Octant::new() };
+		let (canonical, _) = node.canonical_reflection();
+		for mirror in 0..OCTANT_COUNT as u32 {
+			let (reflected_canonical, back) = node.mirrored(mirror).canonical_reflection();
+			assert!(reflected_canonical == canonical, "mirror {}", mirror);
+			assert!(reflected_canonical.mirrored(back) == node.mirrored(mirror));
+		}
+	}
+}
@@ -0,0 +1,139 @@
+use glam::{Vec2, Vec3, Vec4, IVec3};
+use crate::asset::{oct_dag::{OctDag, Octant, VolumeSource, pack_f32_u32, tetrahedron_gradient, true_distance_volume}, noise::{Perlin, PERLIN_SLOPE, fbm_slope}};
+
+/*
+landscape made from noise, a heightfield of fbm with 3d noise pushing the ground around for overhangs and cutting tunnels through it for caves
+caves are kept to a band of heights, as noise can never say much about how far away the next tunnel is, so everything near them is built down to leaves
+y is up, and everything is in build units, so the defaults from new are scaled to the size of the dag
+the same seed and depth always build the same dag, on any number of threads
+distances are only estimates, and are divided by how steep the noise could get so that the builder never cuts off ground
+ */
+pub struct Terrain {
+	pub base_height: f32, //where the ground would be with no hills
+	pub hill_height: f32, //how far the hills go above and below the base
+	pub feature_size: f32, //width of the largest hills
+	pub octaves: u32,
+	pub overhang: f32, //how far the ground can be pushed sideways and up by 3d noise
+	pub cave_size: f32,
+	pub cave_width: f32, //of the noise, 0 for no caves
+	pub cave_bottom: f32,
+	pub cave_top: f32,
+	pub rock_slope: f32, //ground with a normal any less upright than this is bare rock
+	pub snow_height: f32,
+	ground_noise: Perlin,
+	cave_noise: Perlin,
+}
+
+const LACUNARITY: f32 = 2.0;
+const GAIN: f32 = 0.5;
+const DETAIL_OCTAVES: u32 = 3;
+
+const GRASS: Vec4 = Vec4::new(0.25, 0.55, 0.15, 1.0);
+const ROCK: Vec4 = Vec4::new(0.45, 0.42, 0.4, 1.0);
+const SNOW: Vec4 = Vec4::new(0.95, 0.95, 0.97, 1.0);
+
+impl Terrain {
+	pub fn new(seed: u64, max_depth: u32) -> Self {
+		let size = (1u32 << max_depth) as f32;
+		let (base_height, hill_height) = (-size * 0.2, size * 0.3);
+		Self {
+			base_height,
+			hill_height,
+			feature_size: size * 0.75,
+			//octaves past the size of a leaf only add noise to the normals
+			octaves: max_depth.saturating_sub(2).clamp(1, 8),
+			overhang: size * 0.04,
+			cave_size: size * 0.15,
+			cave_width: 0.06,
+			cave_bottom: base_height - hill_height * 0.5,
+			cave_top: base_height + hill_height * 0.2,
+			rock_slope: 0.75,
+			snow_height: base_height + hill_height * 0.45,
+			ground_noise: Perlin::new(seed),
+			cave_noise: Perlin::new(seed ^ 0x5DEECE66D),
+		}
+	}
+
+	pub fn height(&self, x: f32, z: f32) -> f32 {
+		self.base_height + self.hill_height * self.ground_noise.fbm2(Vec2::new(x, z) / self.feature_size, self.octaves, LACUNARITY, GAIN)
+	}
+
+	//estimated distance to the ground, without the caves
+	fn ground(&self, pos: Vec3) -> f32 {
+		let overhang_size = self.feature_size * 0.25;
+		let pushed = self.overhang * self.ground_noise.fbm3(pos / overhang_size, DETAIL_OCTAVES, LACUNARITY, GAIN);
+		let hill_slope = self.hill_height / self.feature_size * PERLIN_SLOPE * fbm_slope(self.octaves, LACUNARITY, GAIN);
+		let overhang_slope = self.overhang / overhang_size * PERLIN_SLOPE * fbm_slope(DETAIL_OCTAVES, LACUNARITY, GAIN);
+		(pos.y - self.height(pos.x, pos.z) + pushed) / ((1.0 + hill_slope * hill_slope).sqrt() + overhang_slope)
+	}
+	//estimated distance to the wall of a cave, negative inside the cave
+	fn cave(&self, pos: Vec3) -> f32 {
+		let band = (pos.y - (self.cave_top + self.cave_bottom) * 0.5).abs() - (self.cave_top - self.cave_bottom) * 0.5;
+		if band > 0.0 {
+			return band;
+		}
+		let noise = self.cave_noise.fbm3(pos / self.cave_size, DETAIL_OCTAVES, LACUNARITY, GAIN);
+		((noise.abs() - self.cave_width) * self.cave_size / (PERLIN_SLOPE * fbm_slope(DETAIL_OCTAVES, LACUNARITY, GAIN))).max(band)
+	}
+	//negative in the ground, the ground being what is under the surface and not in a cave
+	pub fn distance(&self, pos: Vec3) -> f32 {
+		if self.cave_width > 0.0 { self.ground(pos).max(-self.cave(pos)) } else { self.ground(pos) }
+	}
+	pub fn gradient(&self, pos: Vec3) -> Vec3 {
+		tetrahedron_gradient(|pos| self.distance(pos), pos)
+	}
+
+	/*
+	rock on steep ground and in caves, snow up high, grass everywhere else, all a little darker or lighter with noise
+	the shading is stepped so that leaves near each other still share nodes
+	 */
+	pub fn surface_colour(&self, pos: Vec3) -> Vec4 {
+		let upright = self.gradient(pos).normalize_or_zero().y;
+		let in_cave = self.cave_width > 0.0 && -self.cave(pos) > self.ground(pos);
+		let variation = (self.ground_noise.noise3(pos / 3.7) * 2.0).round() / 2.0;
+		let colour = if in_cave || upright < self.rock_slope {
+			ROCK
+		} else if pos.y > self.snow_height + variation * self.hill_height * 0.05 {
+			SNOW
+		} else {
+			GRASS
+		};
+		(colour.truncate() * (0.9 + variation * 0.1)).extend(colour.w)
+	}
+}
+
+impl VolumeSource for Terrain {
+	fn volume(&self, pos: Vec3, _max_level_size: f32) -> Vec4 {
+		true_distance_volume(self.distance(pos), self.gradient(pos))
+	}
+	fn colour(&self, pos: IVec3, _max_level_size: i32) -> Octant {
+		let mut octant = Octant::new();
+		octant.colour = pack_f32_u32(self.surface_colour(pos.as_vec3()));
+		octant
+	}
+	fn distance_is_bound(&self) -> bool {
+		true
+	}
+}
+
+impl OctDag {
+	pub fn new_terrain(seed: u64, max_depth: u32) -> Self {
+		let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+		Self::new_from_source_parallel(max_depth, &Terrain::new(seed, max_depth), threads)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::asset::validate::DagViolation;
+
+	#[test]
+	fn same_seed_same_dag() {
+		let dag = OctDag::new_terrain(7, 6);
+		assert!(dag.nodes == OctDag::new_terrain(7, 6).nodes);
+		assert!(dag.nodes == OctDag::new_from_source(6, &Terrain::new(7, 6)).nodes, "differs with the number of threads");
+		assert!(dag.nodes != OctDag::new_terrain(8, 6).nodes);
+		assert!(dag.validate().iter().all(|violation| !violation.is_fatal() && !matches!(violation, DagViolation::EmptyBranch { .. })));
+	}
+}
@@ -0,0 +1,161 @@
+use std::{fmt, ops::Range};
+use crate::asset::{oct_dag::{OctDag, Octant, OCTANT_COUNT, NULL_INDEX, MASK_8BIT}, split_dag::SplitDagError, symmetric_dag::SymmetricDagError};
+
+/*
+checking a dag before it is trusted, bad indices crash the gpu and cycles or paths that are too deep hang view_trace
+the structural violations are fatal, those about attributes only mean it will look wrong, and are expected after lossy merges
+a node listed in a violation is the one holding the bad octant, found going down from the root, apart from indices which are checked over the whole pool
+ */
+//the size of the stack in view_trace
+pub const MAX_DEPTH: u32 = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DagViolation {
+	NoRoot,
+	DepthOutOfBounds(u32), //max depth is zero or more than the shaders have room for
+	IndexOutOfBounds { node: u32, octant: usize, index: u32 },
+	Cycle { node: u32, octant: usize },
+	TooDeep { node: u32, depth: u32 }, //a node that can be reached at or past max depth, so its octants would be below the leaves
+	EmptyBranch { node: u32, octant: usize }, //points to a node with nothing in it, rather than being empty itself
+	InvisibleLeaf { node: u32, octant: usize }, //a filled leaf with no density, which view_trace can only stop at
+	AggregateMismatch { node: u32, octant: usize }, //has density when nothing below it does, or the other way around
+	//not found by validate, but by turning the dag into a layout that it is too big for
+	TooManyAttributes(u64), //for the split layout
+	TooManyNodes(u32), //for the mirror bits of the symmetric layout
+	BufferTooLarge { size: u64, max: u64 }, //bytes, with the max being what the device can bind
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Visit {
+	New,
+	OnPath,
+	Done,
+}
+
+impl OctDag {
+	pub fn validate(&self) -> Vec<DagViolation> {
+		let mut violations = Vec::new();
+		if self.max_depth == 0 || self.max_depth > MAX_DEPTH {
+			violations.push(DagViolation::DepthOutOfBounds(self.max_depth));
+		}
+		if self.nodes.is_empty() {
+			violations.push(DagViolation::NoRoot);
+			return violations;
+		}
+		violations.extend(self.validate_indices(0..self.nodes.len()));
+		if !violations.is_empty() {
+			return violations;
+		}
+
+		//depth first, marking the nodes on the current path to find cycles, with the order they finish in being children before parents
+		let mut visits = vec![Visit::New; self.nodes.len()];
+		let mut finished = Vec::new();
+		let mut stack = vec![(0u32, 0usize)];
+		visits[0] = Visit::OnPath;
+		while let Some((node, octant)) = stack.last_mut() {
+			if *octant == OCTANT_COUNT {
+				visits[*node as usize] = Visit::Done;
+				finished.push(*node);
+				stack.pop();
+				continue;
+			}
+			let (parent, i) = (*node, *octant);
+			*octant += 1;
+			let child = self.nodes[parent as usize].octants[i].index;
+			if child == NULL_INDEX {
+				continue;
+			}
+			match visits[child as usize] {
+				Visit::New => {
+					visits[child as usize] = Visit::OnPath;
+					stack.push((child, 0));
+				},
+				Visit::OnPath => violations.push(DagViolation::Cycle { node: parent, octant: i }),
+				Visit::Done => {},
+			}
+		}
+		if !violations.is_empty() {
+			return violations;
+		}
+
+		//with no cycles, going through the nodes parents first gives the deepest each can be reached at
+		let mut depths = vec![0; self.nodes.len()];
+		for node in finished.iter().rev() {
+			let depth = depths[*node as usize];
+			if depth >= self.max_depth {
+				violations.push(DagViolation::TooDeep { node: *node, depth });
+			}
+			for (octant, child) in self.nodes[*node as usize].octants.iter().enumerate() {
+				if *child == Octant::new() {
+					continue;
+				}
+				let density = child.normal & MASK_8BIT;
+				if child.index == NULL_INDEX {
+					if density == 0 {
+						violations.push(DagViolation::InvisibleLeaf { node: *node, octant });
+					}
+					continue;
+				}
+				depths[child.index as usize] = depths[child.index as usize].max(depth + 1);
+				let grandchildren = self.nodes[child.index as usize].octants;
+				if grandchildren.iter().all(|grandchild| *grandchild == Octant::new()) {
+					violations.push(DagViolation::EmptyBranch { node: *node, octant });
+				} else if (density > 0) != grandchildren.iter().any(|grandchild| grandchild.normal & MASK_8BIT > 0) {
+					violations.push(DagViolation::AggregateMismatch { node: *node, octant });
+				}
+			}
+		}
+		violations
+	}
+
+	//only that the given nodes point within the pool, for checking the nodes changed by an edit without going over the whole dag
+	pub fn validate_indices(&self, nodes: Range<usize>) -> Vec<DagViolation> {
+		let mut violations = Vec::new();
+		for node in nodes.start..nodes.end.min(self.nodes.len()) {
+			for (octant, child) in self.nodes[node].octants.iter().enumerate() {
+				if child.index != NULL_INDEX && child.index as usize >= self.nodes.len() {
+					violations.push(DagViolation::IndexOutOfBounds { node: node as u32, octant, index: child.index });
+				}
+			}
+		}
+		violations
+	}
+}
+
+impl DagViolation {
+	pub fn is_fatal(&self) -> bool {
+		!matches!(self, DagViolation::EmptyBranch { .. } | DagViolation::InvisibleLeaf { .. } | DagViolation::AggregateMismatch { .. })
+	}
+}
+
+impl fmt::Display for DagViolation {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			DagViolation::NoRoot => write!(f, "no root node"),
+			DagViolation::DepthOutOfBounds(depth) => write!(f, "max depth {} is not within 1 to {}", depth, MAX_DEPTH),
+			DagViolation::IndexOutOfBounds { node, octant, index } => write!(f, "node {} octant {} points to {}, past the end of the pool", node, octant, index),
+			DagViolation::Cycle { node, octant } => write!(f, "node {} octant {} points back up to a node above it", node, octant),
+			DagViolation::TooDeep { node, depth } => write!(f, "node {} can be reached at depth {}, past the leaves", node, depth),
+			DagViolation::EmptyBranch { node, octant } => write!(f, "node {} octant {} points to an empty node", node, octant),
+			DagViolation::InvisibleLeaf { node, octant } => write!(f, "node {} octant {} is a leaf with no density", node, octant),
+			DagViolation::AggregateMismatch { node, octant } => write!(f, "node {} octant {} does not match the density of the node below it", node, octant),
+			DagViolation::TooManyAttributes(count) => write!(f, "{} attributes is too many for the split layout", count),
+			DagViolation::TooManyNodes(index) => write!(f, "node {} does not fit below the mirror bits of the symmetric layout", index),
+			DagViolation::BufferTooLarge { size, max } => write!(f, "{} bytes is more than the {} that can be bound", size, max),
+		}
+	}
+}
+impl From<SplitDagError> for DagViolation {
+	fn from(e: SplitDagError) -> Self {
+		match e {
+			SplitDagError::TooManyAttributes(count) => DagViolation::TooManyAttributes(count),
+		}
+	}
+}
+impl From<SymmetricDagError> for DagViolation {
+	fn from(e: SymmetricDagError) -> Self {
+		match e {
+			SymmetricDagError::TooManyNodes(index) => DagViolation::TooManyNodes(index),
+		}
+	}
+}
@@ -0,0 +1,422 @@
+use std::{fmt, collections::HashMap};
+use glam::{IVec3, Vec3};
+use crate::{asset::{oct_dag::{OctDag, Octant, pack_u8_u32, pack_normal_u32}, voxel_grid::VoxelGrid}, io::read_bin_file};
+
+/*
+importer for magicavoxel .vox files
+https://github.com/ephtracy/voxel-model/blob/master/MagicaVoxel-file-format-vox.txt
+every model is placed using the transforms of the scene graph if there is one, otherwise at the origin
+magicavoxel is z up, so z is swapped in as y, and the whole scene is shifted to sit in the positive corner of the dag
+ */
+const MAGIC: &[u8; 4] = b"VOX ";
+const PALETTE_SIZE: usize = 256;
+
+#[derive(Debug)]
+pub enum VoxError {
+	Io(std::io::Error),
+	BadMagic,
+	Truncated,
+	BadChunk(&'static str),
+	NoVoxels,
+	TooLarge(u32),
+}
+
+struct VoxModel {
+	size: IVec3,
+	voxels: Vec<[u8; 4]>, //x, y, z, palette index
+}
+enum SceneNode {
+	Transform { child: i32, translation: IVec3, rotation: [IVec3; 3], hidden: bool },
+	Group { children: Vec<i32> },
+	Shape { models: Vec<i32> },
+}
+struct VoxScene {
+	models: Vec<VoxModel>,
+	palette: [u32; PALETTE_SIZE],
+	nodes: HashMap<i32, SceneNode>,
+}
+
+impl OctDag {
+	pub fn load_vox(path: &str) -> Result<Self, VoxError> {
+		Self::from_vox_bytes(&read_bin_file(path)?)
+	}
+	pub fn from_vox_bytes(bytes: &[u8]) -> Result<Self, VoxError> {
+		Ok(VoxScene::parse(bytes)?.to_grid()?.build())
+	}
+}
+
+impl VoxScene {
+	fn parse(bytes: &[u8]) -> Result<Self, VoxError> {
+		let mut reader = Reader { bytes, pos: 0 };
+		if reader.take(4)? != MAGIC {
+			return Err(VoxError::BadMagic);
+		}
+		let _version = reader.i32()?;
+		if reader.take(4)? != b"MAIN" {
+			return Err(VoxError::BadChunk("missing MAIN chunk"));
+		}
+		let content_size = reader.size()?;
+		let children_size = reader.size()?;
+		reader.take(content_size)?;
+		let mut reader = Reader { bytes: reader.take(children_size)?, pos: 0 };
+
+		let mut scene = VoxScene {
+			models: Vec::new(),
+			palette: default_palette(),
+			nodes: HashMap::new(),
+		};
+		while reader.pos < reader.bytes.len() {
+			let id: [u8; 4] = reader.take(4)?.try_into().unwrap();
+			let content_size = reader.size()?;
+			let children_size = reader.size()?;
+			let mut chunk = Reader { bytes: reader.take(content_size)?, pos: 0 };
+			reader.take(children_size)?;
+
+			match &id {
+				b"SIZE" => {
+					let size = IVec3::new(chunk.i32()?, chunk.i32()?, chunk.i32()?);
+					scene.models.push(VoxModel { size, voxels: Vec::new() });
+				},
+				b"XYZI" => {
+					let model = scene.models.last_mut().ok_or(VoxError::BadChunk("XYZI before SIZE"))?;
+					let count = chunk.size()?;
+					for _ in 0..count {
+						model.voxels.push(chunk.take(4)?.try_into().unwrap());
+					}
+				},
+				b"RGBA" => {
+					for i in 1..PALETTE_SIZE {
+						let rgba = chunk.take(4)?;
+						scene.palette[i] = pack_u8_u32((rgba[0], rgba[1], rgba[2], rgba[3]));
+					}
+				},
+				b"nTRN" => {
+					let id = chunk.i32()?;
+					let attributes = chunk.dict()?;
+					let child = chunk.i32()?;
+					let _reserved = chunk.i32()?;
+					let _layer = chunk.i32()?;
+					let frame_count = chunk.size()?;
+					let mut translation = IVec3::ZERO;
+					let mut rotation = [IVec3::X, IVec3::Y, IVec3::Z];
+					for frame in 0..frame_count {
+						let frame_attributes = chunk.dict()?;
+						if frame != 0 {
+							continue;
+						}
+						if let Some(value) = frame_attributes.get("_t") {
+							let parts: Vec<i32> = value.split_whitespace().filter_map(|part| part.parse().ok()).collect();
+							if parts.len() != 3 {
+								return Err(VoxError::BadChunk("bad translation"));
+							}
+							translation = IVec3::new(parts[0], parts[1], parts[2]);
+						}
+						if let Some(value) = frame_attributes.get("_r") {
+							rotation = decode_rotation(value.parse().map_err(|_| VoxError::BadChunk("bad rotation"))?)?;
+						}
+					}
+					let hidden = attributes.get("_hidden").is_some_and(|value| value == "1");
+					scene.nodes.insert(id, SceneNode::Transform { child, translation, rotation, hidden });
+				},
+				b"nGRP" => {
+					let id = chunk.i32()?;
+					chunk.dict()?;
+					let child_count = chunk.size()?;
+					let mut children = Vec::with_capacity(child_count);
+					for _ in 0..child_count {
+						children.push(chunk.i32()?);
+					}
+					scene.nodes.insert(id, SceneNode::Group { children });
+				},
+				b"nSHP" => {
+					let id = chunk.i32()?;
+					chunk.dict()?;
+					let model_count = chunk.size()?;
+					let mut models = Vec::with_capacity(model_count);
+					for _ in 0..model_count {
+						models.push(chunk.i32()?);
+						chunk.dict()?;
+					}
+					scene.nodes.insert(id, SceneNode::Shape { models });
+				},
+				_ => {},
+			}
+		}
+		Ok(scene)
+	}
+
+	//every voxel in scene space along with its palette index
+	fn place_voxels(&self) -> Result<Vec<(IVec3, u8)>, VoxError> {
+		let mut placed = Vec::new();
+		if self.nodes.contains_key(&0) {
+			self.place_node(0, [IVec3::X, IVec3::Y, IVec3::Z], IVec3::ZERO, 0, &mut placed)?;
+		} else {
+			for model in &self.models {
+				for voxel in &model.voxels {
+					placed.push((IVec3::new(voxel[0] as i32, voxel[1] as i32, voxel[2] as i32), voxel[3]));
+				}
+			}
+		}
+		Ok(placed)
+	}
+	fn place_node(&self, id: i32, rotation: [IVec3; 3], translation: IVec3, depth: u32, placed: &mut Vec<(IVec3, u8)>) -> Result<(), VoxError> {
+		//the scene graph is a tree, so anything deeper than the node count has to be a cycle
+		if depth as usize > self.nodes.len() {
+			return Err(VoxError::BadChunk("scene graph cycle"));
+		}
+		match self.nodes.get(&id) {
+			Some(SceneNode::Transform { child, translation: local_translation, rotation: local_rotation, hidden }) => {
+				if !hidden {
+					let combined = [0, 1, 2].map(|row| IVec3::new(
+						rotation[row].dot(IVec3::new(local_rotation[0].x, local_rotation[1].x, local_rotation[2].x)),
+						rotation[row].dot(IVec3::new(local_rotation[0].y, local_rotation[1].y, local_rotation[2].y)),
+						rotation[row].dot(IVec3::new(local_rotation[0].z, local_rotation[1].z, local_rotation[2].z))));
+					self.place_node(*child, combined, rotate(rotation, *local_translation) + translation, depth + 1, placed)?;
+				}
+			},
+			Some(SceneNode::Group { children }) => {
+				for child in children {
+					self.place_node(*child, rotation, translation, depth + 1, placed)?;
+				}
+			},
+			Some(SceneNode::Shape { models }) => {
+				for model_id in models {
+					let model = self.models.get(*model_id as usize).ok_or(VoxError::BadChunk("shape references missing model"))?;
+					for voxel in &model.voxels {
+						let local = IVec3::new(voxel[0] as i32, voxel[1] as i32, voxel[2] as i32) - model.size / 2;
+						placed.push((rotate(rotation, local) + translation, voxel[3]));
+					}
+				}
+			},
+			None => return Err(VoxError::BadChunk("scene graph references missing node")),
+		}
+		Ok(())
+	}
+
+	fn to_grid(&self) -> Result<VoxelGrid, VoxError> {
+		let placed: Vec<(IVec3, u8)> = self.place_voxels()?.into_iter()
+			.filter(|(_, index)| *index != 0)
+			.map(|(pos, index)| (IVec3::new(pos.x, pos.z, -pos.y), index))
+			.collect();
+		if placed.is_empty() {
+			return Err(VoxError::NoVoxels);
+		}
+
+		let min = placed.iter().fold(IVec3::splat(i32::MAX), |min, (pos, _)| min.min(*pos));
+		let max = placed.iter().fold(IVec3::splat(i32::MIN), |max, (pos, _)| max.max(*pos));
+		let extent = (max - min + IVec3::ONE).max_element() as u32;
+		let depth = VoxelGrid::depth_for(extent);
+		if depth > 16 {
+			return Err(VoxError::TooLarge(extent));
+		}
+
+		let mut grid = VoxelGrid::new(depth);
+		for (pos, index) in placed {
+			let mut octant = Octant::new();
+			octant.colour = self.palette[index as usize];
+			octant.normal = pack_normal_u32(Vec3::ZERO, 1.0);
+			grid.set((pos - min).as_uvec3(), octant);
+		}
+		grid.fill_normals();
+		Ok(grid)
+	}
+}
+
+struct Reader<'a> {
+	bytes: &'a [u8],
+	pos: usize,
+}
+impl<'a> Reader<'a> {
+	fn take(&mut self, len: usize) -> Result<&'a [u8], VoxError> {
+		if self.pos + len > self.bytes.len() {
+			return Err(VoxError::Truncated);
+		}
+		let slice = &self.bytes[self.pos..self.pos + len];
+		self.pos += len;
+		Ok(slice)
+	}
+	fn i32(&mut self) -> Result<i32, VoxError> {
+		Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+	}
+	fn size(&mut self) -> Result<usize, VoxError> {
+		let value = self.i32()?;
+		if value < 0 {
+			return Err(VoxError::BadChunk("negative size"));
+		}
+		Ok(value as usize)
+	}
+	fn string(&mut self) -> Result<String, VoxError> {
+		let len = self.size()?;
+		Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+	}
+	fn dict(&mut self) -> Result<HashMap<String, String>, VoxError> {
+		let count = self.size()?;
+		let mut dict = HashMap::new();
+		for _ in 0..count {
+			let key = self.string()?;
+			dict.insert(key, self.string()?);
+		}
+		Ok(dict)
+	}
+}
+
+/*
+rotations are packed into a byte as the column of the non zero entry of the first two rows, and the signs of all three
+the column of the third row is whichever is left over
+ */
+fn decode_rotation(packed: u8) -> Result<[IVec3; 3], VoxError> {
+	let first = (packed & 3) as usize;
+	let second = ((packed >> 2) & 3) as usize;
+	if first > 2 || second > 2 || first == second {
+		return Err(VoxError::BadChunk("bad rotation"));
+	}
+	let third = 3 - first - second;
+	let mut rows = [IVec3::ZERO; 3];
+	for (row, (column, sign_bit)) in [(first, 4), (second, 5), (third, 6)].into_iter().enumerate() {
+		rows[row][column] = if packed >> sign_bit & 1 == 1 { -1 } else { 1 };
+	}
+	Ok(rows)
+}
+fn rotate(rotation: [IVec3; 3], pos: IVec3) -> IVec3 {
+	IVec3::new(rotation[0].dot(pos), rotation[1].dot(pos), rotation[2].dot(pos))
+}
+
+/*
+palette used when a file has no RGBA chunk
+a 6 level colour cube from white down, without black, then ramps of blue, green, red and grey that skip the cube levels
+ */
+fn default_palette() -> [u32; PALETTE_SIZE] {
+	let mut palette = [0; PALETTE_SIZE];
+	let cube: [u8; 6] = [0xFF, 0xCC, 0x99, 0x66, 0x33, 0x00];
+	let ramp: [u8; 10] = [0xEE, 0xDD, 0xBB, 0xAA, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+	let mut i = 1;
+	for r in cube {
+		for g in cube {
+			for b in cube {
+				if i < 216 {
+					palette[i] = pack_u8_u32((r, g, b, 0xFF));
+					i += 1;
+				}
+			}
+		}
+	}
+	for channel in [2, 1, 0, 3] {
+		for value in ramp {
+			let mut rgb = [0; 3];
+			if channel == 3 {
+				rgb = [value; 3];
+			} else {
+				rgb[channel] = value;
+			}
+			palette[i] = pack_u8_u32((rgb[0], rgb[1], rgb[2], 0xFF));
+			i += 1;
+		}
+	}
+	palette
+}
+
+impl fmt::Display for VoxError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			VoxError::Io(e) => write!(f, "vox file io error: {}", e),
+			VoxError::BadMagic => write!(f, "not a vox file"),
+			VoxError::Truncated => write!(f, "vox file truncated"),
+			VoxError::BadChunk(reason) => write!(f, "bad vox chunk: {}", reason),
+			VoxError::NoVoxels => write!(f, "vox file has no voxels"),
+			VoxError::TooLarge(extent) => write!(f, "vox scene too large for a dag, extent: {}", extent),
+		}
+	}
+}
+impl std::error::Error for VoxError {}
+impl From<std::io::Error> for VoxError {
+	fn from(e: std::io::Error) -> Self {
+		VoxError::Io(e)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use glam::UVec3;
+	use super::*;
+	use crate::asset::oct_dag::{unpack_u32_normal, MASK_8BIT};
+
+	fn chunk(id: &[u8; 4], content: &[u8], children: &[u8]) -> Vec<u8> {
+		let mut bytes = id.to_vec();
+		bytes.extend_from_slice(&(content.len() as i32).to_le_bytes());
+		bytes.extend_from_slice(&(children.len() as i32).to_le_bytes());
+		bytes.extend_from_slice(content);
+		bytes.extend_from_slice(children);
+		bytes
+	}
+	fn ints(values: &[i32]) -> Vec<u8> {
+		values.iter().flat_map(|value| value.to_le_bytes()).collect()
+	}
+	fn dict(entries: &[(&str, &str)]) -> Vec<u8> {
+		let mut bytes = ints(&[entries.len() as i32]);
+		for text in entries.iter().flat_map(|(key, value)| [key, value]) {
+			bytes.extend_from_slice(&ints(&[text.len() as i32]));
+			bytes.extend_from_slice(text.as_bytes());
+		}
+		bytes
+	}
+
+	/*
+	an L of five voxels, placed by a transform turning it a quarter around z and moving it away from the origin
+	the packed rotation 17 has rows of (0, -1, 0), (1, 0, 0) and (0, 0, 1), so x goes to y and y to -x
+	 */
+	fn scene_bytes() -> Vec<u8> {
+		let voxels: [[u8; 4]; 5] = [[0, 0, 0, 1], [1, 0, 0, 1], [2, 0, 0, 2], [0, 1, 0, 3], [0, 0, 1, 4]];
+		let mut palette = vec![0u8; PALETTE_SIZE * 4];
+		palette[..16].copy_from_slice(&[255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 255, 255, 255, 255]);
+
+		let mut children = chunk(b"SIZE", &ints(&[3, 2, 2]), &[]);
+		let mut xyzi = ints(&[voxels.len() as i32]);
+		xyzi.extend(voxels.iter().flatten());
+		children.extend(chunk(b"XYZI", &xyzi, &[]));
+		children.extend(chunk(b"RGBA", &palette, &[]));
+		let transform = [ints(&[0]), dict(&[]), ints(&[1, -1, -1, 1]), dict(&[("_r", "17"), ("_t", "10 20 30")])].concat();
+		children.extend(chunk(b"nTRN", &transform, &[]));
+		let shape = [ints(&[1]), dict(&[]), ints(&[1, 0]), dict(&[])].concat();
+		children.extend(chunk(b"nSHP", &shape, &[]));
+
+		let mut bytes = MAGIC.to_vec();
+		bytes.extend_from_slice(&ints(&[150]));
+		bytes.extend(chunk(b"MAIN", &[], &children));
+		bytes
+	}
+
+	#[test]
+	fn import_rotated_model() {
+		let scene = VoxScene::parse(&scene_bytes()).unwrap();
+		let grid = scene.to_grid().unwrap();
+		let dag = OctDag::from_vox_bytes(&scene_bytes()).unwrap();
+		assert_eq!(grid.max_depth, 2);
+
+		/*
+		worked out by hand, from the model centred on its size, turned, moved, made y up as (x, z, -y), then moved into the positive corner
+		along the model's x is towards -z, along its y is towards -x, and up in the model is up
+		 */
+		let (red, green, blue, white) = ((255, 0, 0, 255), (0, 255, 0, 255), (0, 0, 255, 255), (255, 255, 255, 255));
+		let expected = [(UVec3::new(1, 0, 2), red), (UVec3::new(1, 0, 1), red), (UVec3::new(1, 0, 0), green),
+			(UVec3::new(0, 0, 2), blue), (UVec3::new(1, 1, 2), white)];
+		assert_eq!(grid.voxels.len(), expected.len());
+		for (pos, colour) in expected {
+			let octant = grid.get(pos).unwrap_or_else(|| panic!("nothing at {}", pos));
+			assert_eq!(octant.colour, pack_u8_u32(colour), "at {}", pos);
+			assert_eq!(octant.normal & MASK_8BIT, MASK_8BIT);
+			assert!(dag.voxel(pos) == *octant, "at {}", pos);
+		}
+		//the voxel on top of the corner has nothing above it
+		assert!(unpack_u32_normal(grid.get(UVec3::new(1, 1, 2)).unwrap().normal).y > 0.5);
+
+		let filled = (0..64).filter(|i| dag.voxel(UVec3::new(i % 4, i / 4 % 4, i / 16)) != Octant::new()).count();
+		assert_eq!(filled, expected.len());
+	}
+
+	#[test]
+	fn bad_files() {
+		let bytes = scene_bytes();
+		assert!(matches!(OctDag::from_vox_bytes(b"VOY \x96\0\0\0"), Err(VoxError::BadMagic)));
+		assert!(matches!(OctDag::from_vox_bytes(&bytes[..bytes.len() - 1]), Err(VoxError::Truncated)));
+	}
+}
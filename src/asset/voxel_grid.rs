@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use glam::{UVec3, IVec3, Vec3};
+use crate::asset::oct_dag::{OctDag, Node, Octant, DedupTable, pack_normal_u32, MASK_8BIT};
+
+/*
+sparse set of leaf voxels that a dag can be built from bottom up, for sources that are already voxels rather than volumes
+positions are in [0, 2^max_depth) along each axis, and octants are numbered the same as the dag, x being the lowest bit
+ */
+pub struct VoxelGrid {
+	pub max_depth: u32,
+	pub voxels: HashMap<UVec3, Octant>,
+}
+
+impl VoxelGrid {
+	pub fn new(max_depth: u32) -> Self {
+		if max_depth == 0 || max_depth > 16 {
+			panic!("depth out of bounds");
+		}
+		Self {
+			max_depth,
+			voxels: HashMap::new(),
+		}
+	}
+	//smallest depth that fits the given number of voxels along an axis
+	pub fn depth_for(extent: u32) -> u32 {
+		let mut depth = 1;
+		while (1 << depth) < extent {
+			depth += 1;
+		}
+		depth
+	}
+	pub fn size(&self) -> u32 {
+		1 << self.max_depth
+	}
+	pub fn in_bounds(&self, pos: IVec3) -> bool {
+		pos.cmpge(IVec3::ZERO).all() && pos.cmplt(IVec3::splat(self.size() as i32)).all()
+	}
+	pub fn set(&mut self, pos: UVec3, octant: Octant) {
+		self.voxels.insert(pos, octant);
+	}
+	pub fn get(&self, pos: UVec3) -> Option<&Octant> {
+		self.voxels.get(&pos)
+	}
+	pub fn is_filled(&self, pos: IVec3) -> bool {
+		self.in_bounds(pos) && self.voxels.contains_key(&pos.as_uvec3())
+	}
+
+	/*
+	replaces the normal of every voxel with the direction away from its filled neighbours, keeping the density
+	voxels with nothing around them or fully enclosed are left pointing nowhere
+	 */
+	pub fn fill_normals(&mut self) {
+		let mut normals = Vec::with_capacity(self.voxels.len());
+		for pos in self.voxels.keys() {
+			let mut normal = Vec3::ZERO;
+			for x in -1..=1 {
+				for y in -1..=1 {
+					for z in -1..=1 {
+						let offset = IVec3::new(x, y, z);
+						if offset != IVec3::ZERO && !self.is_filled(pos.as_ivec3() + offset) {
+							normal += offset.as_vec3().normalize();
+						}
+					}
+				}
+			}
+			normals.push((*pos, normal.normalize_or_zero()));
+		}
+		for (pos, normal) in normals {
+			let octant = self.voxels.get_mut(&pos).unwrap();
+			octant.normal = pack_normal_u32(normal, 0.0) | (octant.normal & MASK_8BIT);
+		}
+	}
+
+	pub fn build(&self) -> OctDag {
+		self.build_with_report().0
+	}
+
+	/*
+	builds one level at a time from the leaves up, grouping octants into their parent nodes
+	nodes whose octants are all the same leaf are collapsed into that leaf, the same as solid regions in fill_oct
+	the nodes of each level are added in morton order so that the result does not depend on the hash map ordering
+	 */
+	pub fn build_with_report(&self) -> (OctDag, DedupTable) {
+		let mut dag = OctDag{nodes: vec![Node::new()], max_depth: self.max_depth};
+		let mut dedup = DedupTable::new(self.max_depth);
+
+		let mut level: Vec<(UVec3, Octant)> = self.voxels.iter().map(|(pos, octant)| (*pos, *octant)).collect();
+		for depth in (1..=self.max_depth).rev() {
+			let mut parents: HashMap<UVec3, Node> = HashMap::new();
+			for (pos, octant) in level {
+				parents.entry(pos >> 1).or_insert_with(Node::new).octants[child_index(pos)] = octant;
+			}
+			if depth == 1 {
+				if let Some(root) = parents.get(&UVec3::ZERO) {
+					dag.nodes[0] = *root;
+				}
+				break;
+			}
+
+			let mut positions: Vec<UVec3> = parents.keys().copied().collect();
+			positions.sort_by_key(|pos| morton(*pos));
+			level = positions.into_iter().map(|pos| {
+				let node = parents[&pos];
+				let octant = match node.uniform_leaf() {
+					Some(leaf) => leaf,
+					None => {
+						let mut octant = node.aggregate(depth - 1);
+						octant.index = dedup.insert(&mut dag.nodes, node, depth - 1);
+						octant
+					},
+				};
+				(pos, octant)
+			}).collect();
+		}
+		(dag, dedup)
+	}
+}
+
+fn child_index(pos: UVec3) -> usize {
+	((pos.x & 1) | (pos.y & 1) << 1 | (pos.z & 1) << 2) as usize
+}
+fn morton(pos: UVec3) -> u64 {
+	let spread = |value: u32| {
+		let mut value = value as u64 & 0x1F_FFFF;
+		value = (value | value << 32) & 0x001F_0000_0000_FFFF;
+		value = (value | value << 16) & 0x001F_0000_FF00_00FF;
+		value = (value | value << 8) & 0x100F_00F0_0F00_F00F;
+		value = (value | value << 4) & 0x10C3_0C30_C30C_30C3;
+		(value | value << 2) & 0x1249_2492_4924_9249
+	};
+	spread(pos.x) | spread(pos.y) << 1 | spread(pos.z) << 2
+}
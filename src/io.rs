@@ -1,12 +1,20 @@
 use std::fs::File;
-use std::io::Read;
+use std::io::{Read, Write, Result};
 use std::path::Path;
 
-pub fn read_bin_file(loc: &str) -> Vec<u8> {
+pub fn read_bin_file(loc: &str) -> Result<Vec<u8>> {
     let path = Path::new(loc);
-    let mut file = File::open(&path).expect("file open failure");
+    let mut file = File::open(path)?;
     let mut buffer = Vec::new();
 
-    file.read_to_end(&mut buffer).expect("failed to read file");
-    return buffer;
-}	
\ No newline at end of file
+    file.read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
+
+pub fn write_bin_file(loc: &str, data: &[u8]) -> Result<()> {
+    let path = Path::new(loc);
+    let mut file = File::create(path)?;
+
+    file.write_all(data)?;
+    Ok(())
+}
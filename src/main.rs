@@ -15,7 +15,7 @@ mod io;
 
 use crate::{asset::oct_dag::{OctDag, TestDagType},
 	logic::logic::Logic,
-	render::render::Render,
+	render::render::{Render, DagLayout},
 	window::Window};
 
 const WINDOW_WIDTH: u16 = 1920; 
@@ -27,10 +27,20 @@ fn main() {
 	let mut event_loop = EventLoop::new();
 	let mut window = Window::new(WINDOW_WIDTH, WINDOW_HEIGHT, &event_loop);	
 
-	let mut logic = Logic::new(OctDag::new_test(TestDagType::Pillar, 6));
+	let (dag, dedup) = OctDag::new_test_with_report(TestDagType::Pillar, 6);
+	dedup.print_report();
+	let mut logic = Logic::new(dag);
 	logic.dag.print_size();
 		
-	let mut render = Render::new(&window, &logic);
+	let mut render = match Render::new(&window, &logic, DagLayout::Nodes) {
+		Ok(render) => render,
+		Err(violations) => {
+			for violation in violations {
+				eprintln!("{}", violation);
+			}
+			return;
+		},
+	};
 	render.print_state();
 
 	event_loop.run_return(move |event, _, control_flow| {
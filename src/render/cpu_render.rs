@@ -0,0 +1,225 @@
+use glam::{Vec2, Vec3, Vec4, IVec2, IVec3, UVec3, BVec3, Vec2Swizzles, Vec3Swizzles, Vec4Swizzles};
+use crate::{asset::oct_dag::{OctDag, Octant, NULL_INDEX, MASK_8BIT}, render::{shader_structs::ViewInputData, image::Image}};
+
+/*
+view_trace run on the cpu, for getting images without a gpu and checking what the gpu gives back
+this is a line by line copy of exper_shaders.wgsl, so any change there has to be made here too
+the camera is in the same units as the shader, where the root spans -MAX_SIZE to MAX_SIZE no matter the depth of the dag
+ */
+const MAX_DEPTH: usize = 16;
+const MAX_SIZE: i32 = 0x008000;
+const MAX_ITERS: u32 = 256;
+const MIN_TRANS: f32 = 0.001;
+const FOV: f32 = 1.1;
+const POSITIVE_MASKS: UVec3 = UVec3::new(1, 2, 4);
+
+//rows are shared out between the threads
+pub fn render_cpu(dag: &OctDag, view: &ViewInputData, width: u32, height: u32, threads: usize) -> Image {
+	let mut image = Image::new(width, height);
+	let row_size = (width * 4) as usize;
+	if row_size == 0 || height == 0 {
+		return image;
+	}
+	let rows_per_thread = (height as usize).div_ceil(threads.max(1));
+
+	std::thread::scope(|scope| {
+		for (chunk_index, chunk) in image.pixels.chunks_mut(row_size * rows_per_thread).enumerate() {
+			scope.spawn(move || {
+				for (row_index, row) in chunk.chunks_mut(row_size).enumerate() {
+					let y = (chunk_index * rows_per_thread + row_index) as u32;
+					for x in 0..width {
+						let rgba = view_trace(dag, view, UVec3::new(x, y, 0), Vec2::new(width as f32, height as f32));
+						let start = (x * 4) as usize;
+						row[start..start + 4].copy_from_slice(&rgba.to_array().map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8));
+					}
+				}
+			});
+		}
+	});
+	image
+}
+
+fn view_trace(dag: &OctDag, camera: &ViewInputData, global_id: UVec3, dims: Vec2) -> Vec4 {
+	let lod_factor: f32 = (FOV / dims.x).sin() * 2.0;
+
+	let direction: Vec3 = rotation(get_view_vec(global_id.xy().as_vec2(), dims), camera.rads.xyz()).normalize();
+	let inverse_vec: Vec3 = Vec3::ONE / direction;
+	let mut i_center: IVec3 = IVec3::splat(MAX_SIZE);
+	let mut center: Vec3 = Vec3::ZERO;
+	let mut position: Vec3 = camera.pos.xyz();
+	let mut level_size: i32 = MAX_SIZE;
+	let mut depth: i32 = 0;
+	let mut stack = [0u32; MAX_DEPTH + 1];
+	stack[depth as usize] = 0;
+	let mut octant_index: u32 = calculate_octant(position, center, direction);
+	let mut moving_up: bool = false;
+	let mut bottom: bool = false;
+
+	let mut transmittance: Vec4 = Vec4::ONE;
+	let mut rgb: Vec3 = Vec3::ZERO;
+	let mut octant: Octant = fetch_octant(dag, 0, octant_index);
+	//zeroed rather than empty, the same as an uninitialised var in wgsl
+	let mut previous_octant = Octant{index: 0, colour: 0, normal: 0, extra: 0};
+
+	let mut iters: u32 = 0;
+	let mut length: f32 = 0.0;
+
+	loop { if depth < 0 || transmittance.w < MIN_TRANS || iters > MAX_ITERS {break;}
+		bottom |= (iters as f32 / MAX_ITERS as f32).powf(8.0) * MAX_SIZE as f32 > level_size as f32;
+		if !moving_up && !bottom {
+			stack[(depth + 1) as usize] = octant.index;
+			depth += 1;
+			level_size >>= 1;
+			let positive = (UVec3::splat(octant_index) & POSITIVE_MASKS).cmpeq(POSITIVE_MASKS);
+			i_center += level_size * (-1 + 2 * bools_to_ivec(positive));
+			center = (i_center - MAX_SIZE).as_vec3();
+
+			octant_index = calculate_octant(position, center, direction);
+			octant = fetch_octant(dag, stack[depth as usize], octant_index);
+			bottom = octant.index == NULL_INDEX;
+		}
+		if bottom {
+			previous_octant = octant;
+		}
+		if moving_up || bottom {
+			let to_zero: Vec3 = (center - position) * inverse_vec;
+			let mut valid: BVec3 = to_zero.cmpgt(Vec3::ZERO) & position.cmpne(center);
+			valid &= (to_zero.cmple(to_zero.zxy()) | !bvec_zxy(valid)) & (to_zero.cmplt(to_zero.yzx()) | !bvec_yzx(valid));
+			let mut next_position = Vec3::ZERO;
+			if valid.x {
+				next_position = Vec3::new(center.x, position.y + direction.y * to_zero.x, position.z + direction.z * to_zero.x);
+			} else if valid.y {
+				next_position = position + direction * to_zero.y;
+				next_position.y = center.y;
+			} else if valid.z {
+				next_position = Vec3::new(position.x + direction.x * to_zero.z, position.y + direction.y * to_zero.z, center.z);
+			}
+
+			moving_up = (center - next_position).abs().cmpgt(Vec3::splat(level_size as f32)).any() || !valid.any();
+
+			let on_boundary = ((i_center - level_size) % (level_size * 4)).cmpeq(IVec3::ZERO);
+			i_center += (level_size * (-1 + 2 * bools_to_ivec(on_boundary))) * moving_up as i32;
+			center = (i_center - MAX_SIZE).as_vec3();
+			depth -= moving_up as i32;
+			level_size <<= moving_up as u32;
+
+			let len = (next_position - position).dot(direction);
+			length += len * (!moving_up) as i32 as f32;
+			if !moving_up {
+				position = next_position;
+			}
+
+			//past the root there is nothing left to fetch, the loop ends before the octant is used
+			if depth < 0 {
+				break;
+			}
+			octant_index = calculate_octant(position, center, direction);
+			octant = fetch_octant(dag, stack[depth as usize], octant_index);
+			bottom = octant.index == NULL_INDEX
+				|| length * lod_factor > level_size as f32;
+
+			let octant_rgba = unpack4x8unorm_local(previous_octant.colour) / 255.0;
+			let mut octant_norm = unpack_normal(previous_octant.normal);
+			if (octant_norm.w > 0.0 && octant_rgba.w > 0.0) && !moving_up {
+				//light falls off exponentially, so going through two halves of something takes out as much as going through all of it
+				//low densities go out to very long distances, so what is faint up close can still be see through in a big octant far away
+				octant_norm.w = (1.0 - (-len / (3000.0
+					* ((1.0 - octant_norm.w) / octant_norm.w + (1.0 - octant_rgba.w))).max(0.0001)).exp())
+					* transmittance.w;
+				transmittance.w -= octant_norm.w;
+				rgb += octant_rgba.xyz() * octant_norm.w;
+			}
+		}
+		iters += 1;
+	}
+
+	let octant_rgba = unpack4x8unorm_local(previous_octant.colour) / 255.0;
+	if octant_rgba.w > 0.0 {
+		rgb += octant_rgba.xyz() * transmittance.w;
+	}
+	rgb.extend(1.0)
+}
+
+//view_trace can read past the end of the dag, such as when the camera starts in an empty octant, where the gpu gives back zeros
+fn fetch_octant(dag: &OctDag, node_index: u32, octant_index: u32) -> Octant {
+	dag.nodes.get(node_index as usize).map_or(Octant{index: 0, colour: 0, normal: 0, extra: 0}, |node| node.octants[octant_index as usize])
+}
+fn calculate_octant(position: Vec3, center: Vec3, direction: Vec3) -> u32 {
+	let positive = position.cmpgt(center) | (position.cmpeq(center) & direction.cmpgt(Vec3::ZERO));
+	POSITIVE_MASKS.dot(bools_to_ivec(positive).as_uvec3())
+}
+
+fn get_view_vec(coords: Vec2, dims: Vec2) -> Vec3 {
+	let thetas: Vec2 = Vec2::new(-((coords.x - dims.x / 2.0) / dims.x * FOV * 2.0),
+		(coords.y - dims.y / 2.0) / dims.x * FOV * 2.0);
+	Vec3::new(thetas.x.cos(), 0.0, thetas.x.sin())
+		.cross(Vec3::new(0.0, thetas.y.cos(), thetas.y.sin()))
+}
+fn rotation(direction: Vec3, radians: Vec3) -> Vec3 {
+	let new_vec = Vec3::new(direction.x,
+		radians.y.cos() * direction.y + radians.y.sin() * direction.z,
+		radians.y.cos() * direction.z - radians.y.sin() * direction.y);
+	Vec3::new(radians.x.cos() * new_vec.x - radians.x.sin() * new_vec.z,
+		new_vec.y,
+		radians.x.cos() * new_vec.z + radians.x.sin() * new_vec.x)
+}
+fn unpack4x8unorm_local(x: u32) -> Vec4 {
+	Vec4::new(((x >> 24) & MASK_8BIT) as f32,
+		((x >> 16) & MASK_8BIT) as f32,
+		((x >> 8) & MASK_8BIT) as f32,
+		(x & MASK_8BIT) as f32)
+}
+//the normal in xyz and the density in w, see pack_normal_u32 in oct_dag.rs
+fn unpack_normal(x: u32) -> Vec4 {
+	//each 12 bit coordinate is shifted up to the top then back down signed to carry its sign through
+	let folded = (IVec2::new(x as i32 >> 20, (x << 12) as i32 >> 20).as_vec2() / 2047.0).clamp(Vec2::NEG_ONE, Vec2::ONE);
+	let z = 1.0 - folded.x.abs() - folded.y.abs();
+	let mut xy = folded;
+	if z < 0.0 {
+		xy = (1.0 - folded.yx().abs()) * Vec2::select(folded.cmplt(Vec2::ZERO), Vec2::NEG_ONE, Vec2::ONE);
+	}
+	Vec3::new(xy.x, xy.y, z).normalize().extend((x & MASK_8BIT) as f32 / 255.0)
+}
+
+fn bools_to_ivec(bools: BVec3) -> IVec3 {
+	IVec3::new(bools.x as i32, bools.y as i32, bools.z as i32)
+}
+fn bvec_zxy(bools: BVec3) -> BVec3 {
+	BVec3::new(bools.z, bools.x, bools.y)
+}
+fn bvec_yzx(bools: BVec3) -> BVec3 {
+	BVec3::new(bools.y, bools.z, bools.x)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{asset::oct_dag::TestDagType, render::render::{Render, DagLayout}};
+
+	/*
+	the same frames drawn by exper_shaders.wgsl and the shaders for the other layouts, with only the rounding of floats on the gpu allowed to differ
+	the size is not a multiple of the workgroups, so the edges are drawn by workgroups that hang over
+	the software adapter is asked for, so that it runs the same without a gpu
+	 */
+	#[test]
+	fn matches_gpu() {
+		let (width, height) = (61, 45);
+		let views = [
+			ViewInputData { pos: Vec4::ZERO, rads: Vec4::ZERO },
+			ViewInputData { pos: Vec4::new(5000.0, -3000.0, 2000.0, 0.0), rads: Vec4::new(0.8, 0.3, 0.0, 0.0) },
+			ViewInputData { pos: Vec4::new(-12000.0, 8000.0, -20000.0, 0.0), rads: Vec4::new(-2.2, -0.5, 0.0, 0.0) },
+		];
+		for dag_type in [TestDagType::Box, TestDagType::Pillar] {
+			let dag = OctDag::new_test(dag_type, 6);
+			let cpu = views.map(|view| render_cpu(&dag, &view, width, height, 4));
+			for layout in [DagLayout::Nodes, DagLayout::Split, DagLayout::Compact, DagLayout::Symmetric] {
+				let mut render = Render::new_headless(&dag, width, height, layout, true).unwrap().expect("no fallback adapter");
+				for (view, cpu) in views.iter().zip(&cpu) {
+					let gpu = render.render_to_image(*view);
+					assert!(cpu.pixels.iter().any(|channel| *channel != 0), "nothing drawn");
+					assert!(cpu.max_difference(&gpu) <= 2, "difference of {}", cpu.max_difference(&gpu));
+				}
+			}
+		}
+	}
+}
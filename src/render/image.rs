@@ -0,0 +1,46 @@
+use std::{fs::File, io::BufWriter};
+use crate::io::write_bin_file;
+
+//rgba8 pixels, row by row from the top left, the same as the output texture
+pub struct Image {
+	pub width: u32,
+	pub height: u32,
+	pub pixels: Vec<u8>,
+}
+
+impl Image {
+	pub fn new(width: u32, height: u32) -> Self {
+		Self {
+			width,
+			height,
+			pixels: vec![0; (width * height * 4) as usize],
+		}
+	}
+	pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+		let start = ((y * self.width + x) * 4) as usize;
+		self.pixels[start..start + 4].try_into().unwrap()
+	}
+
+	pub fn write_png(&self, path: &str) -> Result<(), png::EncodingError> {
+		let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), self.width, self.height);
+		encoder.set_color(png::ColorType::Rgba);
+		encoder.set_depth(png::BitDepth::Eight);
+		encoder.write_header()?.write_image_data(&self.pixels)
+	}
+	//binary ppm, which has no alpha so it is dropped
+	pub fn write_ppm(&self, path: &str) -> std::io::Result<()> {
+		let mut data = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+		for pixel in self.pixels.chunks_exact(4) {
+			data.extend_from_slice(&pixel[..3]);
+		}
+		write_bin_file(path, &data)
+	}
+
+	//the largest difference between any channel of the two images, for comparing renders
+	pub fn max_difference(&self, other: &Image) -> u8 {
+		if self.width != other.width || self.height != other.height {
+			return u8::MAX;
+		}
+		self.pixels.iter().zip(other.pixels.iter()).map(|(a, b)| a.abs_diff(*b)).max().unwrap_or(0)
+	}
+}
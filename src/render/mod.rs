@@ -1,3 +1,5 @@
 pub mod render;
 pub(self) mod shader_processing;
-pub mod shader_structs;
\ No newline at end of file
+pub mod shader_structs;
+pub mod image;
+pub mod cpu_render;
\ No newline at end of file
@@ -1,8 +1,8 @@
-use wgpu::{*, util::{*}};
+use wgpu::*;
 use glam::{Vec3, Vec4, UVec4};
-use std::{fs::File, io::Write};
+use std::{fs::File, io::Write, ops::Range};
 use pollster::FutureExt;
-use crate::{asset::oct_dag::{Node}, logic::logic::Logic, window::Window, render::{shader_structs::*, shader_processing::*}};
+use crate::{asset::{oct_dag::{OctDag, Node}, validate::DagViolation, split_dag::SplitDag, compact_dag::CompactDag, symmetric_dag::SymmetricDag}, logic::logic::Logic, window::Window, render::{shader_structs::*, shader_processing::*, image::Image}};
 
 const REPORT_AFTER_FRAMES: u64 = 500;
 //const TARGET_FRAMES: u32 = 300;
@@ -13,18 +13,42 @@ const VIEW_INPUT_INDEX: u32 = unique_index!();
 const TEMPORAL_INPUT_INDEX: u32 = unique_index!();
 const VIEW_DATA_INDEX: u32 = unique_index!();
 const OUTPUT_TEXTURE_INDEX: u32 = unique_index!();
+const ATTRIBUTE_INDEX: u32 = unique_index!();
 
 const WORK_GROUP_WIDTH: u32 = 8;
 const WORK_GROUP_HEIGHT: u32 = 8;
 
 const LIGHT_GRID_DIMENSION: usize = 128;
+//dag buffers are made this much bigger than needed so that edits can add nodes without reallocating
+const DAG_HEADROOM: f32 = 1.5;
 
 //macro_rules! SHADERS_PATH {() => {"shaders.wgsl"};}
 //so far exper runs better. need to double checl non flattened valid mask
 //not sure where to put shaders later if they arenet baked in via macro
 macro_rules! SHADERS_PATH {() => {"exper_shaders.wgsl"};}
+macro_rules! SPLIT_SHADERS_PATH {() => {"split_shaders.wgsl"};}
+macro_rules! COMPACT_SHADERS_PATH {() => {"compact_shaders.wgsl"};}
+macro_rules! SYMMETRIC_SHADERS_PATH {() => {"symmetric_shaders.wgsl"};}
 macro_rules! VIEW_TRACE_ENTRY {() => {"view_trace"};}
 
+//how the dag is laid out on the gpu, each with its own version of view trace
+pub enum DagLayout {
+	Nodes,
+	Split,
+	Compact,
+	Symmetric,
+}
+
+/*
+what changed in the dag since it was last uploaded
+ranges are of nodes, and are only used with the plain node layout, the others get encoded and uploaded whole
+after an edit the dirty nodes are the root and everything past the old length, after garbage collection it is all of it
+ */
+pub enum DirtyNodes {
+	Ranges(Vec<Range<usize>>),
+	All,
+}
+
 pub struct Render {
 	integrals: RenderIntegrals,
 
@@ -36,7 +60,9 @@ pub struct Render {
 	view_trace_bindgroups: [BindGroup; 2],
 	//output_view: TextureView,
 
-	//dag_buffer: Buffer,
+	layout: DagLayout,
+	dag_buffers: Vec<(u32, Buffer)>,
+	view_buffers: [Buffer; 2],
 	view_input_uniform: Buffer,
 	output_texture: Texture,
 
@@ -50,18 +76,39 @@ pub struct Render {
 }
 
 impl Render {
-	pub fn new(window: &Window, state: &Logic) -> Self {
-		let integrals = RenderIntegrals::new(window);
+	//the dag is checked before anything is made on the gpu, with the fatal violations given back if it can not be uploaded
+	pub fn new(window: &Window, state: &Logic, layout: DagLayout) -> Result<Self, Vec<DagViolation>> {
+		let contents = checked_dag_contents(&layout, &state.dag)?;
+		Self::with_integrals(RenderIntegrals::new(window), contents, layout)
+	}
+
+	/*
+	rendering into output_texture alone, with no window or surface, for servers and tests
+	images are got back with render_to_image, render cannot be used as there is nothing to present to
+	the fallback adapter is the software one where there is one, for machines without a gpu
+	none if no adapter or device could be had, which is only tried once the dag has been checked
+	 */
+	pub fn new_headless(dag: &OctDag, width: u32, height: u32, layout: DagLayout, force_fallback_adapter: bool) -> Result<Option<Self>, Vec<DagViolation>> {
+		let contents = checked_dag_contents(&layout, dag)?;
+		match RenderIntegrals::new_headless(width, height, force_fallback_adapter) {
+			Some(integrals) => Self::with_integrals(integrals, contents, layout).map(Some),
+			None => Ok(None),
+		}
+	}
 
-		let dag_byte_array = unsafe{std::slice::from_raw_parts(state.dag.nodes[..].as_ptr() as *const u8,
-			std::mem::size_of::<Node>() * state.dag.nodes.len())};
+	//fails only if a dag buffer would be too big to bind
+	fn with_integrals(integrals: RenderIntegrals, contents: DagContents, layout: DagLayout) -> Result<Self, Vec<DagViolation>> {
+		let shader_source = match layout {
+			DagLayout::Nodes => include_str!(SHADERS_PATH!()),
+			DagLayout::Split => include_str!(SPLIT_SHADERS_PATH!()),
+			DagLayout::Compact => include_str!(COMPACT_SHADERS_PATH!()),
+			DagLayout::Symmetric => include_str!(SYMMETRIC_SHADERS_PATH!()),
+		};
 		//must have trait POD on it, however that allows for things like bit fiddling
 		//let byte_dag_arr = bytemuck::bytes_of(&state.dag.nodes);
-		let dag_buffer = integrals.device.create_buffer_init(&BufferInitDescriptor{
-			label: Some("dag buffer"),
-			usage: BufferUsages::COPY_DST | BufferUsages::STORAGE,
-			contents: dag_byte_array,
-		});
+		let dag_buffers: Vec<(u32, Buffer)> = contents.into_iter()
+			.map(|(binding, contents, label)| Ok((binding, create_dag_buffer(&integrals, &contents, label)?)))
+			.collect::<Result<_, DagViolation>>().map_err(|violation| vec![violation])?;
 		let view_input_uniform = integrals.device.create_buffer( &BufferDescriptor {
 			label: Some(" buffer"),
 			mapped_at_creation: false,
@@ -71,8 +118,8 @@ impl Render {
 		let output_texture = integrals.device.create_texture(&TextureDescriptor {
 			label: Some("output texture"),
 			size: Extent3d {
-				width: window.inner_width(),
-				height: window.inner_height(), 
+				width: integrals.surface_config.width,
+				height: integrals.surface_config.height,
 				depth_or_array_layers: 1,
 			},
 			mip_level_count: 1,
@@ -107,8 +154,8 @@ impl Render {
 
 		let shader_module = integrals.device.create_shader_module(ShaderModuleDescriptor{
 			label: Some("shader module"),
-			source: ShaderSource::Wgsl(shader_preprocessor(include_str!(SHADERS_PATH!()).into(), map_constants!(
-				GROUP_INDEX, DAG_INDEX, VIEW_INPUT_INDEX, VIEW_DATA_INDEX, TEMPORAL_INPUT_INDEX, OUTPUT_TEXTURE_INDEX, LIGHT_GRID_DIMENSION, ["{}u"; WORK_GROUP_WIDTH, WORK_GROUP_HEIGHT]
+			source: ShaderSource::Wgsl(shader_preprocessor(shader_source.into(), map_constants!(
+				GROUP_INDEX, DAG_INDEX, ATTRIBUTE_INDEX, VIEW_INPUT_INDEX, VIEW_DATA_INDEX, TEMPORAL_INPUT_INDEX, OUTPUT_TEXTURE_INDEX, LIGHT_GRID_DIMENSION, ["{}u"; WORK_GROUP_WIDTH, WORK_GROUP_HEIGHT]
 			)).into()),
 		});
 		let pipeline_layout = {
@@ -129,8 +176,9 @@ impl Render {
 			bind_group_layouts: &[
 				&integrals.device.create_bind_group_layout(&BindGroupLayoutDescriptor {
 					label: Some("view trace bind group layout"),
-					entries: &[
-						buffer_entry(DAG_INDEX, BufferBindingType::Storage { read_only: true }),
+					entries: &dag_buffers.iter()
+						.map(|(binding, _)| buffer_entry(*binding, BufferBindingType::Storage { read_only: true }))
+						.chain([
 						buffer_entry(VIEW_INPUT_INDEX, BufferBindingType::Uniform),
 						buffer_entry(VIEW_DATA_INDEX, BufferBindingType::Storage { read_only: false }),
 						BindGroupLayoutEntry {
@@ -143,7 +191,7 @@ impl Render {
 							},
 							count: None,
 						},
-					],
+					]).collect::<Vec<_>>(),
 				}),
 			],
 		})};
@@ -155,39 +203,18 @@ impl Render {
 		});	
 
 		
-		let view_trace_bindgroups = {
-			let create_view_trace_bindgroup = |view: &Buffer| {
-				//seems binding resource has hidden lifetime, not sure how to access it for lifetime specified function, thus macro
-				macro_rules! create_bindgroup_entry {
-					($binding:expr, $resource:expr) => {
-						BindGroupEntry {
-							binding: $binding,
-							resource: $resource,
-						}
-				};}
-				integrals.device.create_bind_group(&BindGroupDescriptor {
-					label: Some("view trace bindgroup"),
-					layout: &view_trace_pipeline.get_bind_group_layout(GROUP_INDEX),
-					entries: &[
-						create_bindgroup_entry!(DAG_INDEX, dag_buffer.as_entire_binding()),
-						create_bindgroup_entry!(VIEW_INPUT_INDEX, view_input_uniform.as_entire_binding()),
-						create_bindgroup_entry!(VIEW_DATA_INDEX, view.as_entire_binding()),
-						create_bindgroup_entry!(OUTPUT_TEXTURE_INDEX, BindingResource::TextureView(&output_texture.create_view(&wgpu::TextureViewDescriptor::default()))),
-					],
-				})
-			};
-			[create_view_trace_bindgroup(&view_buffers[0]), create_view_trace_bindgroup(&view_buffers[1])]
-		};
-
+		let view_trace_bindgroups = create_view_trace_bindgroups(&integrals, &view_trace_pipeline, &dag_buffers, &view_input_uniform, &view_buffers, &output_texture);
 
-		return Self {
+		return Ok(Self {
          integrals: integrals,
 
 			view_trace_pipeline: view_trace_pipeline,
 			view_trace_bindgroups: view_trace_bindgroups,
 			//output_view: output_view,
 
-			//dag_buffer: dag_buffer,
+			layout,
+			dag_buffers,
+			view_buffers,
 			output_texture: output_texture,
 			view_input_uniform: view_input_uniform,
 
@@ -199,7 +226,7 @@ impl Render {
 			ave_frame_time: 0.0,
 			max_frame_time: 0,
 			min_frame_time: 0,
-		}
+		})
 	}
 
 
@@ -218,17 +245,9 @@ impl Render {
 				rads: Into::<Vec4>::into((state.camera_orientaion_vec3(), 0.0)),
 		});
 
-		let mut encoder = self.integrals.device.create_command_encoder(&CommandEncoderDescriptor{label: Some("view trace render pass encoder")});
-		
-		let mut view_trace_pass = encoder.begin_compute_pass(&ComputePassDescriptor { label: Some("view trace pass")});
-		view_trace_pass.set_pipeline(&self.view_trace_pipeline);
-		view_trace_pass.set_bind_group(GROUP_INDEX, &self.view_trace_bindgroups[(self.frame_counter % 2) as usize], &[]);
-		view_trace_pass.dispatch_workgroups(self.integrals.surface_config.width / WORK_GROUP_WIDTH, self.integrals.surface_config.height / WORK_GROUP_HEIGHT, 1);
-
-		drop(view_trace_pass);
-
+		let mut encoder = self.view_trace_encoder();
 
-		let surface_texture = self.integrals.surface.get_current_texture()?;
+		let surface_texture = self.integrals.surface.as_ref().expect("headless render has no surface, use render_to_image").get_current_texture()?;
 		encoder.copy_texture_to_texture(
 			ImageCopyTexture {
 				aspect: TextureAspect::All,
@@ -286,7 +305,114 @@ impl Render {
 		return Ok(());
 	}
 
-	pub fn update_dag(&mut self) {}
+	/*
+	renders a frame and waits for it to be copied back
+	rows of a texture copy have to be a multiple of COPY_BYTES_PER_ROW_ALIGNMENT, so the padding is taken off each row after
+	 */
+	pub fn render_to_image(&mut self, view: ViewInputData) -> Image {
+		self.update_camera(view);
+		let mut encoder = self.view_trace_encoder();
+
+		let (width, height) = (self.integrals.surface_config.width, self.integrals.surface_config.height);
+		let row_size = width * 4;
+		let padded_row_size = row_size.next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT);
+		let readback_buffer = self.integrals.device.create_buffer(&BufferDescriptor {
+			label: Some("readback buffer"),
+			mapped_at_creation: false,
+			usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+			size: (padded_row_size * height) as u64,
+		});
+		encoder.copy_texture_to_buffer(
+			ImageCopyTexture {
+				aspect: TextureAspect::All,
+				texture: &self.output_texture,
+				mip_level: 0,
+				origin: Origin3d::ZERO,
+			},
+			ImageCopyBuffer {
+				buffer: &readback_buffer,
+				layout: ImageDataLayout {
+					offset: 0,
+					bytes_per_row: std::num::NonZeroU32::new(padded_row_size),
+					rows_per_image: None,
+				},
+			},
+			Extent3d {
+				width,
+				height,
+				depth_or_array_layers: 1,
+			},
+		);
+		self.integrals.queue.submit(std::iter::once(encoder.finish()));
+		self.frame_counter += 1;
+
+		let slice = readback_buffer.slice(..);
+		slice.map_async(MapMode::Read, |result| result.expect("failed to map readback buffer"));
+		self.integrals.device.poll(Maintain::Wait);
+		let mut image = Image::new(width, height);
+		for (row, padded_row) in image.pixels.chunks_exact_mut(row_size as usize).zip(slice.get_mapped_range().chunks_exact(padded_row_size as usize)) {
+			row.copy_from_slice(&padded_row[..row_size as usize]);
+		}
+		readback_buffer.unmap();
+		image
+	}
+
+	//an encoder with the view trace pass for this frame already in it
+	fn view_trace_encoder(&self) -> CommandEncoder {
+		let mut encoder = self.integrals.device.create_command_encoder(&CommandEncoderDescriptor{label: Some("view trace render pass encoder")});
+
+		let mut view_trace_pass = encoder.begin_compute_pass(&ComputePassDescriptor { label: Some("view trace pass")});
+		view_trace_pass.set_pipeline(&self.view_trace_pipeline);
+		view_trace_pass.set_bind_group(GROUP_INDEX, &self.view_trace_bindgroups[(self.frame_counter % 2) as usize], &[]);
+		view_trace_pass.dispatch_workgroups(self.integrals.surface_config.width.div_ceil(WORK_GROUP_WIDTH), self.integrals.surface_config.height.div_ceil(WORK_GROUP_HEIGHT), 1);
+		drop(view_trace_pass);
+		encoder
+	}
+
+	/*
+	uploads only what changed where it can, otherwise the whole dag
+	buffers that are too small are made again with headroom, which means the bind groups have to be made again too
+	when only some nodes changed, only their indices are checked, as going over the whole dag each edit costs as much as uploading it
+		the rest of the checks are still run in debug builds
+		ranges that go past the end of the dag are not trusted, and everything is uploaded instead
+	nothing is uploaded if there are fatal violations, or if a buffer would be too big to bind
+	 */
+	pub fn update_dag(&mut self, dag: &OctDag, dirty: DirtyNodes) -> Result<(), Vec<DagViolation>> {
+		if let (DagLayout::Nodes, DirtyNodes::Ranges(ranges)) = (&self.layout, &dirty) {
+			let (_, buffer) = &self.dag_buffers[0];
+			//the whole dag fitting in the buffer means every range within it does too
+			if std::mem::size_of_val(&dag.nodes[..]) as u64 <= buffer.size()
+				&& ranges.iter().all(|range| range.start <= range.end && range.end <= dag.nodes.len()) {
+				let violations: Vec<DagViolation> = ranges.iter().flat_map(|range| dag.validate_indices(range.clone())).collect();
+				if !violations.is_empty() {
+					return Err(violations);
+				}
+				if cfg!(debug_assertions) {
+					check_dag(dag)?;
+				}
+				for range in ranges {
+					self.integrals.queue.write_buffer(buffer, (range.start * std::mem::size_of::<Node>()) as u64, slice_bytes(&dag.nodes[range.clone()]));
+				}
+				return Ok(());
+			}
+		}
+
+		let mut reallocated = false;
+		for ((binding, contents, label), (buffer_binding, buffer)) in checked_dag_contents(&self.layout, dag)?.into_iter().zip(self.dag_buffers.iter_mut()) {
+			debug_assert_eq!(binding, *buffer_binding);
+			if contents.len() as u64 > buffer.size() {
+				*buffer = create_dag_buffer(&self.integrals, &contents, label).map_err(|violation| vec![violation])?;
+				reallocated = true;
+			} else {
+				self.integrals.queue.write_buffer(buffer, 0, &contents);
+			}
+		}
+		if reallocated {
+			self.view_trace_bindgroups = create_view_trace_bindgroups(&self.integrals, &self.view_trace_pipeline,
+				&self.dag_buffers, &self.view_input_uniform, &self.view_buffers, &self.output_texture);
+		}
+		Ok(())
+	}
 
 	pub fn update_camera(&mut self, pos: ViewInputData) {
 		self.integrals.queue.write_buffer(&self.view_input_uniform, 0, unsafe{ std::slice::from_raw_parts((&pos as *const ViewInputData) as *const u8, std::mem::size_of::<ViewInputData>()) });
@@ -307,8 +433,94 @@ impl Render {
 	}
 }
 
+//a bad index or a cycle can take down the gpu, so nothing that would is ever uploaded
+fn check_dag(dag: &OctDag) -> Result<(), Vec<DagViolation>> {
+	let violations: Vec<DagViolation> = dag.validate().into_iter().filter(|violation| violation.is_fatal()).collect();
+	if violations.is_empty() { Ok(()) } else { Err(violations) }
+}
+
+//the binding, bytes and label of each buffer the layout needs
+type DagContents = Vec<(u32, Vec<u8>, &'static str)>;
+
+//the contents, once the dag has been checked and fits the layout
+fn checked_dag_contents(layout: &DagLayout, dag: &OctDag) -> Result<DagContents, Vec<DagViolation>> {
+	check_dag(dag)?;
+	Ok(match layout {
+		DagLayout::Nodes => vec![(DAG_INDEX, slice_bytes(&dag.nodes[..]).to_vec(), "dag buffer")],
+		DagLayout::Split => {
+			let split = SplitDag::from_dag(dag).map_err(|e| vec![e.into()])?;
+			vec![(DAG_INDEX, slice_bytes(&split.topology[..]).to_vec(), "topology buffer"),
+				(ATTRIBUTE_INDEX, slice_bytes(&split.attributes[..]).to_vec(), "attribute buffer")]
+		},
+		DagLayout::Compact => {
+			let compact = CompactDag::from_dag(dag);
+			vec![(DAG_INDEX, slice_bytes(&compact.words[..]).to_vec(), "compact dag buffer")]
+		},
+		DagLayout::Symmetric => {
+			let symmetric = SymmetricDag::from_dag(dag).map_err(|e| vec![e.into()])?;
+			vec![(DAG_INDEX, slice_bytes(&symmetric.nodes[..]).to_vec(), "symmetric dag buffer")]
+		},
+	})
+}
+
+//a storage buffer with room to grow, within what can be bound, holding the contents at the start
+fn create_dag_buffer(integrals: &RenderIntegrals, contents: &[u8], label: &str) -> Result<Buffer, DagViolation> {
+	let size = dag_buffer_size(contents.len() as u64, integrals.device.limits().max_storage_buffer_binding_size as u64)?;
+	let buffer = integrals.device.create_buffer(&BufferDescriptor {
+		label: Some(label),
+		mapped_at_creation: false,
+		usage: BufferUsages::COPY_DST | BufferUsages::STORAGE,
+		size,
+	});
+	integrals.queue.write_buffer(&buffer, 0, contents);
+	Ok(buffer)
+}
+
+//the headroom is left out where it would go past the binding limit, contents that are past it on their own can not be rendered
+fn dag_buffer_size(needed: u64, max_size: u64) -> Result<u64, DagViolation> {
+	let needed_aligned = needed.next_multiple_of(COPY_BUFFER_ALIGNMENT);
+	if needed_aligned > max_size {
+		return Err(DagViolation::BufferTooLarge { size: needed, max: max_size });
+	}
+	let size = ((needed as f32 * DAG_HEADROOM) as u64).max(COPY_BUFFER_ALIGNMENT * 4).next_multiple_of(COPY_BUFFER_ALIGNMENT);
+	Ok(size.min(max_size / COPY_BUFFER_ALIGNMENT * COPY_BUFFER_ALIGNMENT).max(needed_aligned))
+}
+
+fn create_view_trace_bindgroups(integrals: &RenderIntegrals, view_trace_pipeline: &ComputePipeline,
+	dag_buffers: &[(u32, Buffer)], view_input_uniform: &Buffer, view_buffers: &[Buffer; 2], output_texture: &Texture) -> [BindGroup; 2] {
+
+	let create_view_trace_bindgroup = |view: &Buffer| {
+		//seems binding resource has hidden lifetime, not sure how to access it for lifetime specified function, thus macro
+		macro_rules! create_bindgroup_entry {
+			($binding:expr, $resource:expr) => {
+				BindGroupEntry {
+					binding: $binding,
+					resource: $resource,
+				}
+		};}
+		integrals.device.create_bind_group(&BindGroupDescriptor {
+			label: Some("view trace bindgroup"),
+			layout: &view_trace_pipeline.get_bind_group_layout(GROUP_INDEX),
+			entries: &dag_buffers.iter()
+				.map(|(binding, buffer)| create_bindgroup_entry!(*binding, buffer.as_entire_binding()))
+				.chain([
+				create_bindgroup_entry!(VIEW_INPUT_INDEX, view_input_uniform.as_entire_binding()),
+				create_bindgroup_entry!(VIEW_DATA_INDEX, view.as_entire_binding()),
+				create_bindgroup_entry!(OUTPUT_TEXTURE_INDEX, BindingResource::TextureView(&output_texture.create_view(&wgpu::TextureViewDescriptor::default()))),
+			]).collect::<Vec<_>>(),
+		})
+	};
+	[create_view_trace_bindgroup(&view_buffers[0]), create_view_trace_bindgroup(&view_buffers[1])]
+}
+
+//raw bytes of a slice of repr(C) structs for uploading
+fn slice_bytes<T>(data: &[T]) -> &[u8] {
+	unsafe{std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data))}
+}
+
+//the surface is none when headless, the config is then only kept for the size of the output
 struct RenderIntegrals {
-	pub surface: Surface,
+	pub surface: Option<Surface>,
 	pub adapter: Adapter,
 	pub device: Device,
 	pub queue: Queue,
@@ -360,11 +572,94 @@ impl RenderIntegrals {
 		surface.configure(&device, &surface_config);
         
 		Self {
-			surface: surface,
+			surface: Some(surface),
 			adapter: adapter,
 			device: device,
 			queue: queue,
 			surface_config: surface_config,
 		}
 	} 
+
+	pub fn new_headless(width: u32, height: u32, force_fallback_adapter: bool) -> Option<Self> {
+		let instance = Instance::new(InstanceDescriptor{
+			backends: Backends::all(),
+			dx12_shader_compiler: Default::default(),
+		});
+		let adapter = instance.request_adapter(&RequestAdapterOptions{
+			power_preference: PowerPreference::HighPerformance,
+			compatible_surface: None,
+			force_fallback_adapter,
+		}).block_on()?;
+		let (device, queue) = adapter.request_device(&DeviceDescriptor{
+			features: Features::default(),
+			//the same as the window, so that what works headless works there too
+			limits: Limits::default(),
+			label: Some("headless device"),
+		}, None).block_on().ok()?;
+
+		Some(Self {
+			surface: None,
+			adapter,
+			device,
+			queue,
+			surface_config: SurfaceConfiguration {
+				usage: TextureUsages::COPY_DST,
+				format: TextureFormat::Rgba8Unorm,
+				width,
+				height,
+				present_mode: PresentMode::Fifo,
+				alpha_mode: CompositeAlphaMode::Opaque,
+				view_formats: vec![],
+			},
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::asset::oct_dag::{Octant, TestDagType};
+
+	#[test]
+	fn invalid_dag_is_refused() {
+		let mut dag = OctDag::new_test(TestDagType::Box, 4);
+		let last = dag.nodes.len() - 1;
+		dag.nodes[last].octants[0] = Octant{index: dag.nodes.len() as u32, ..Octant::new()};
+		let violations = Render::new_headless(&dag, 8, 8, DagLayout::Nodes, false).err().unwrap();
+		assert!(violations.iter().all(|violation| matches!(violation, DagViolation::IndexOutOfBounds { .. })));
+
+		let mut dag = OctDag::new_test(TestDagType::Box, 4);
+		let mut render = Render::new_headless(&dag, 8, 8, DagLayout::Nodes, true).unwrap().expect("no fallback adapter");
+		dag.nodes[last].octants[0].index = dag.nodes.len() as u32;
+		assert!(render.update_dag(&dag, DirtyNodes::Ranges(vec![Range { start: last, end: last + 1 }])).is_err());
+		dag.nodes[last].octants[0].index = 0;
+		assert!(render.update_dag(&dag, DirtyNodes::All).is_err());
+	}
+
+	#[test]
+	fn buffer_past_binding_limit_is_refused() {
+		let max = Limits::default().max_storage_buffer_binding_size as u64;
+		assert_eq!(dag_buffer_size(max + 1, max), Err(DagViolation::BufferTooLarge { size: max + 1, max }));
+		//the headroom is what gives way near the limit
+		assert_eq!(dag_buffer_size(max, max), Ok(max));
+		assert_eq!(dag_buffer_size(max - 100, max), Ok(max));
+		assert_eq!(dag_buffer_size(1000, max), Ok(1500));
+	}
+
+	//ranges past the end of the dag, or past the end of the buffer, upload everything rather than slicing
+	#[test]
+	fn bad_ranges_upload_everything() {
+		let dag = OctDag::new_test(TestDagType::Box, 4);
+		let mut render = Render::new_headless(&dag, 8, 8, DagLayout::Nodes, true).unwrap().expect("no fallback adapter");
+		let size = render.dag_buffers[0].1.size();
+		let nodes = dag.nodes.len();
+		assert!(render.update_dag(&dag, DirtyNodes::Ranges(vec![Range { start: nodes - 1, end: nodes + 10 }])).is_ok());
+		assert_eq!(render.dag_buffers[0].1.size(), size);
+
+		let mut bigger = OctDag::new_test(TestDagType::Box, 4);
+		let extra = bigger.nodes.len() * 2;
+		bigger.nodes.extend(std::iter::repeat_n(bigger.nodes[nodes - 1], extra));
+		assert!(render.update_dag(&bigger, DirtyNodes::Ranges(vec![Range { start: nodes, end: nodes + extra }])).is_ok());
+		assert!(render.dag_buffers[0].1.size() >= std::mem::size_of_val(&bigger.nodes[..]) as u64);
+	}
 }
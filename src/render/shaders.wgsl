@@ -50,6 +50,10 @@ const POSITIVE_MASKS: vec3<u32> = vec3<u32>(POSITIVE_X, POSITIVE_Y, POSITIVE_Z);
 @compute @workgroup_size(8u, 8u)
 fn view_trace(@builtin(global_invocation_id) global_id: vec3<u32>) {
 	let dims = vec2<f32>(textureDimensions(output));
+	//the last workgroups hang over the edge when the size is not a multiple of theirs
+	if (any(global_id.xy >= vec2<u32>(dims))) {
+		return;
+	}
 	var lod_factor: f32 = sin(FOV / dims.x);
 
 	var level_size: i32 = MAX_SIZE;
@@ -74,7 +78,7 @@ fn view_trace(@builtin(global_invocation_id) global_id: vec3<u32>) {
 	
 	loop { if(depth < 0 || transmittance.w < MIN_TRANS || iters > MAX_ITERS) {break;}
 		octant_index = dot(POSITIVE_MASKS,
-			vec3<u32>(position > center || ((position == center) && direction > 0.0))); 	
+			vec3<u32>(position > center | ((position == center) & direction > 0.0))); 	
 
 		let octant: Octant = dag.nodes[stack[depth]].octants[octant_index];
 		let bottom: bool = octant.index == NULL_INDEX 
@@ -93,8 +97,8 @@ fn view_trace(@builtin(global_invocation_id) global_id: vec3<u32>) {
 			}
 
 			let to_zero: vec3<f32> = (center - position) * inverse_vec;
-			var valid: vec3<bool> = to_zero > 0.0 && position != center;
-			valid &= (to_zero <= to_zero.zxy || !valid.zxy) && (to_zero < to_zero.yzx || !valid.yzx);
+			var valid: vec3<bool> = to_zero > 0.0 & position != center;
+			valid &= (to_zero <= to_zero.zxy | !valid.zxy) & (to_zero < to_zero.yzx | !valid.yzx);
 			//let next_position = select(position + direction * dot(vec3<f32>(valid), to_zero), center, valid); 
 			//for some reason this runs more stable than the select
 			//bench mark putting valid back into the following if block
@@ -136,10 +140,12 @@ fn view_trace(@builtin(global_invocation_id) global_id: vec3<u32>) {
 				//or the other option being that an alpha value is stored with the transmittance and that is used as the thershold?
 
 			var octant_rgba = unpack4x8unorm_local(previous_octant.colour) / 255.0; 
-			var octant_norm = unpack4x8unorm_local(previous_octant.normal) / 255.0;
+			var octant_norm = unpack_normal(previous_octant.normal);
 			if((octant_norm.w > 0.0 && octant_rgba.w > 0.0) && !moving_up) {
-				octant_norm.w = (len / (len + (3000.0 
-					* ((1.0 - octant_norm.w) + (1.0 - octant_rgba.w))))) 
+				//light falls off exponentially, so going through two halves of something takes out as much as going through all of it
+				//low densities go out to very long distances, so what is faint up close can still be see through in a big octant far away
+				octant_norm.w = (1.0 - exp(-len / max(3000.0 
+					* ((1.0 - octant_norm.w) / octant_norm.w + (1.0 - octant_rgba.w)), 0.0001))) 
 					* transmittance.w;
 				transmittance.w = transmittance.w - octant_norm.w;
 				rgb = rgb + octant_rgba.xyz * vec3<f32>(octant_norm.w);
@@ -176,3 +182,15 @@ fn unpack4x8unorm_local(x: u32) -> vec4<f32> {
 		(x >> 8u) & MASK_8BIT,
 		x & MASK_8BIT));
 }
+
+//the normal in xyz and the density in w, see pack_normal_u32 in oct_dag.rs
+fn unpack_normal(x: u32) -> vec4<f32> {
+	//each 12 bit coordinate is shifted up to the top then back down signed to carry its sign through
+	let folded = clamp(vec2<f32>(vec2<i32>(bitcast<i32>(x) >> 20u, bitcast<i32>(x << 12u) >> 20u)) / 2047.0, vec2<f32>(-1.0), vec2<f32>(1.0));
+	let z = 1.0 - abs(folded.x) - abs(folded.y);
+	var xy = folded;
+	if(z < 0.0) {
+		xy = (1.0 - abs(folded.yx)) * select(vec2<f32>(1.0), vec2<f32>(-1.0), folded < vec2<f32>(0.0));
+	}
+	return vec4<f32>(normalize(vec3<f32>(xy, z)), f32(x & MASK_8BIT) / 255.0);
+}
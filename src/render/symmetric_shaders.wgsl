@@ -0,0 +1,231 @@
+type DagIndex = u32; 
+
+//TODO: change to using a vec4, may need to define alignment, but would likely help alot with read speeds
+//perhaps create functions for grabbing info to help with readability
+//type Octant = vec4<u32>;
+struct Octant {
+	index: DagIndex, //null is u32 max, 0xFFFFFFFF
+	colour: u32, //rgba
+	normal: u32, //24 bits normal, 8 for density //may want to change such that density is alpha, that more closely resmbles how the colours add
+	extra: u32, //, 8 for shine,  16 for frames // 8 would be for indexing in larger tree 
+}
+struct Node { //vec3 ints, x = index, y = colour, z = addition info
+	octants: array<Octant, 8>,
+}
+//the octant as seen from the frame of the ray, with the mirror its child is read with, see symmetric_dag.rs
+struct MirroredOctant {
+	index: DagIndex,
+	colour: u32,
+	normal: u32,
+	extra: u32,
+	mirror: u32,
+}
+struct Dag {
+	nodes: array<Node>,
+}
+
+struct ViewInput {
+	position: vec4<f32>, //x y z pad
+	direction: vec4<f32>, //yaw, pitch, roll, fov 
+}
+
+struct ViewData {
+	position: vec3<f32>,
+	len: f32,
+	rgba: u32,
+	normal: u32,	
+}
+
+const MAX_DEPTH: i32 = 16;
+const NULL_INDEX: DagIndex = 0xFFFFFFFFu;
+const MASK_8BIT: u32 = 0x000000FFu;
+const MIRROR_SHIFT: u32 = 29u;
+const INDEX_MASK: u32 = 0x1FFFFFFFu;
+const MAX_SIZE: i32 = 0x008000; //can go up to 20 bits before excessive precission loss
+const MAX_ITERS: u32 = 256u;
+const MIN_TRANS: f32 = 0.001;
+const FOV: f32 = 1.1;
+
+const POSITIVE_X: u32 = 1u;
+const POSITIVE_Y: u32 = 2u;
+const POSITIVE_Z: u32 = 4u;
+const NEGATIVE_OCTANT: u32 = 0u;
+const POSITIVE_MASKS: vec3<u32> = vec3<u32>(POSITIVE_X, POSITIVE_Y, POSITIVE_Z);
+
+@group(/*GROUP_INDEX*/0/**/) @binding(/*DAG_INDEX*/0/**/) var<storage, read> dag: Dag;
+@group(/*GROUP_INDEX*/0/**/) @binding(/*VIEW_INPUT_INDEX*/1/**/) var<uniform> camera: ViewInput;
+@group(/*GROUP_INDEX*/0/**/) @binding(/*VIEW_DATA_INDEX*/2/**/) var<storage, write> view: ViewData;
+@group(/*GROUP_INDEX*/0/**/) @binding(/*OUTPUT_TEXTURE_INDEX*/3/**/) var output: texture_storage_2d<rgba8unorm, write>;
+
+@compute @workgroup_size(/*WORK_GROUP_WIDTH*/8u/**/, /*WORK_GROUP_HEIGHT*/8u/**/)
+fn view_trace(@builtin(global_invocation_id) global_id: vec3<u32>) {
+	let dims = vec2<f32>(textureDimensions(output));
+	//the last workgroups hang over the edge when the size is not a multiple of theirs
+	if (any(global_id.xy >= vec2<u32>(dims))) {
+		return;
+	}
+	var lod_factor: f32 = sin(FOV / dims.x) * 2.0;
+
+	var direction: vec3<f32> = normalize(rotation(get_view_vec(vec2<f32>(global_id.xy), dims), camera.direction.xyz));
+	var inverse_vec: vec3<f32> = vec3<f32>(1.0) / direction;
+	var i_center: vec3<i32> = vec3<i32>(MAX_SIZE);
+	var center: vec3<f32> = vec3<f32>(0.0);
+	var position: vec3<f32> = camera.position.xyz; 
+
+	var level_size: i32 = MAX_SIZE;
+	var depth: i32 = 0;
+	var stack: array<DagIndex, MAX_DEPTH>;
+	stack[depth] = 0u; 
+	var mirror_stack: array<u32, MAX_DEPTH>;
+	mirror_stack[depth] = 0u;
+	var octant_index: u32 = calculate_octant(position, center, direction);
+	var moving_up: bool = false;
+	var bottom: bool = false;
+		
+	var transmittance: vec4<f32> = vec4<f32>(1.0);
+	var rgb: vec3<f32> = vec3<f32>(0.0);
+	var octant: MirroredOctant = fetch_octant(0u, 0u, octant_index);
+	var previous_octant: MirroredOctant;
+
+	var iters: u32 = 0u;
+	var length: f32 = 0.0;
+	
+	loop { if(depth < 0 || transmittance.w < MIN_TRANS || iters > MAX_ITERS) {break;}
+		bottom |= pow(f32(iters) / f32(MAX_ITERS), 8.0) * f32(MAX_SIZE) > f32(level_size);
+		if (!moving_up && !bottom) {
+			stack[depth + 1] = octant.index;
+			mirror_stack[depth + 1] = octant.mirror;
+			depth += 1;
+			level_size >>= 1u;
+			//shifts and mask with one would take away cast
+			//may also consider moving to a bool mask that, that new centers and new oct index can be calculated off of directly
+			i_center += level_size * (-1 + 2 * vec3<i32>((octant_index & POSITIVE_MASKS) == POSITIVE_MASKS)); 
+			center = vec3<f32>(i_center - MAX_SIZE);
+
+			octant_index = calculate_octant(position, center, direction);
+			octant = fetch_octant(stack[depth], mirror_stack[depth], octant_index);
+			bottom = octant.index == NULL_INDEX;
+		}  
+		if(bottom) {
+			previous_octant = octant;
+		}
+		if (moving_up || bottom) {
+
+			let to_zero: vec3<f32> = (center - position) * inverse_vec;
+			var valid: vec3<bool> = to_zero > 0.0 & position != center;
+			valid &= (to_zero <= to_zero.zxy | !valid.zxy) & (to_zero < to_zero.yzx | !valid.yzx);
+			//let next_position = select(position + direction * dot(vec3<f32>(valid), to_zero), center, valid); 
+			//for some reason this runs more stable than the select despite techinically having more optionally ran code
+			//bench mark putting valid back into the following if block
+			//while the averages seem fine, the lows are very volatile
+			var next_position = vec3<f32>(0.0);
+			if(valid.x) {
+				next_position = vec3<f32>(center.x, position.yz + direction.yz * to_zero.x);
+			} else if(valid.y) {
+				next_position = position + direction * to_zero.y;
+				next_position.y = center.y;
+			} else if(valid.z) {
+				next_position = vec3<f32>(position.xy + direction.xy * to_zero.z, center.z);
+			}
+
+			moving_up = any(abs(center - next_position) > f32(level_size)) || all(!valid);
+	
+			//moving up
+			//this branchless seems to run better
+			i_center += (level_size * (-1 + 2 * vec3<i32>((i_center - level_size) % (level_size * 4) == 0))) * i32(moving_up); 
+			center = vec3<f32>(i_center - MAX_SIZE);
+			depth -= i32(moving_up); 
+			level_size <<= u32(moving_up);
+			
+			//moving forward
+			let len = dot(next_position.xyz - position, direction);
+			length += (len * f32(!moving_up));
+			if(!moving_up) {
+				position = next_position.xyz;
+			}
+
+			octant_index = calculate_octant(position, center, direction);
+			octant = fetch_octant(stack[depth], mirror_stack[depth], octant_index);
+			bottom = octant.index == NULL_INDEX 
+				|| length * lod_factor > f32(level_size);
+		
+			//the density should add colours, while the alpha should subtract(ie only let through its colour, that being said think of water and how it only refracts blue)
+			//suppositione there is a colour behind a coloured smoke, the colours should add but if behind glass, the glass wont allow the coluor through
+			//the alpha is still kept with the rgba vec so that may create an option
+			//should probably create a doubled up system, where one colour represents the current pixel colour, and the other represents the alpha colour that lets colour through?
+			//with the double system, one is the pixel rgb, the other is the transmittance rgb
+				//still it is tricky to convey the alpha
+				//may be achieved by using a threshold, 
+				//ie if the transmittance is already at or under, or som function approximating the idea, the transmittance calculated with the alpha, then its effects are minimized 
+				//or the other option being that an alpha value is stored with the transmittance and that is used as the thershold?
+
+			var octant_rgba = unpack4x8unorm_local(previous_octant.colour) / 255.0; 
+			var octant_norm = unpack_normal(previous_octant.normal);
+			if((octant_norm.w > 0.0 && octant_rgba.w > 0.0) && !moving_up) {
+				//light falls off exponentially, so going through two halves of something takes out as much as going through all of it
+				//low densities go out to very long distances, so what is faint up close can still be see through in a big octant far away
+				octant_norm.w = (1.0 - exp(-len / max(3000.0 
+					* ((1.0 - octant_norm.w) / octant_norm.w + (1.0 - octant_rgba.w)), 0.0001))) 
+					* transmittance.w;
+				transmittance.w = transmittance.w - octant_norm.w;
+				rgb = rgb + octant_rgba.xyz * vec3<f32>(octant_norm.w);
+			}
+		}
+		iters += 1u;
+	}
+
+	var octant_rgba = unpack4x8unorm_local(previous_octant.colour) / 255.0; 
+	if( octant_rgba.w > 0.0) {
+		rgb = rgb + octant_rgba.xyz * vec3<f32>(transmittance.w);
+	}
+	textureStore(output, vec2<i32>(global_id.xy), vec4<f32>(rgb, 1.0));
+}
+
+//rather than flipping the ray into the frame of a mirrored node, the octant it is in is flipped into the node
+//normals are not flipped back as nothing here reads their direction
+fn fetch_octant(node_index: u32, mirror: u32, octant_index: u32) -> MirroredOctant {
+	let stored = dag.nodes[node_index].octants[octant_index ^ mirror];
+	var octant = MirroredOctant(stored.index, stored.colour, stored.normal, stored.extra, 0u);
+	if(stored.index != NULL_INDEX) {
+		octant.index = stored.index & INDEX_MASK;
+		octant.mirror = mirror ^ (stored.index >> MIRROR_SHIFT);
+	}
+	return octant;
+}
+fn calculate_octant(position: vec3<f32>, center: vec3<f32>, direction: vec3<f32>) -> u32 {
+	return dot(POSITIVE_MASKS, vec3<u32>(position > center | ((position == center) & direction > 0.0))); 	
+}
+
+//vectors generated stretch vertically but not horizontally? should check with square res
+fn get_view_vec(coords: vec2<f32>, dims: vec2<f32>) -> vec3<f32> {
+	let thetas: vec2<f32> = vec2<f32>(-((coords.x - dims.x / 2.0) / dims.x * FOV * 2.0),
+		((coords.y - dims.y / 2.0) / dims.x * FOV * 2.0));
+	return cross(vec3<f32>(cos(thetas.x), 0.0, sin(thetas.x)),
+		vec3<f32>(0.0, cos(thetas.y), sin(thetas.y)));
+}
+fn rotation(direction: vec3<f32>, radians: vec3<f32>) -> vec3<f32> {
+	var new_vec = vec3<f32>(direction.x,
+		cos(radians.y) * direction.y + sin(radians.y) * direction.z,
+		cos(radians.y) * direction.z - sin(radians.y) * direction.y);
+	return vec3<f32>(cos(radians.x) * new_vec.x - sin(radians.x) * new_vec.z,
+		new_vec.y,
+		cos(radians.x) * new_vec.z + sin(radians.x) * new_vec.x);
+}
+fn unpack4x8unorm_local(x: u32) -> vec4<f32> {
+	return vec4<f32>(vec4<u32>((x >> 24u) & MASK_8BIT,
+		(x >> 16u) & MASK_8BIT,
+		(x >> 8u) & MASK_8BIT,
+		x & MASK_8BIT));
+}
+
+//the normal in xyz and the density in w, see pack_normal_u32 in oct_dag.rs
+fn unpack_normal(x: u32) -> vec4<f32> {
+	//each 12 bit coordinate is shifted up to the top then back down signed to carry its sign through
+	let folded = clamp(vec2<f32>(vec2<i32>(bitcast<i32>(x) >> 20u, bitcast<i32>(x << 12u) >> 20u)) / 2047.0, vec2<f32>(-1.0), vec2<f32>(1.0));
+	let z = 1.0 - abs(folded.x) - abs(folded.y);
+	var xy = folded;
+	if(z < 0.0) {
+		xy = (1.0 - abs(folded.yx)) * select(vec2<f32>(1.0), vec2<f32>(-1.0), folded < vec2<f32>(0.0));
+	}
+	return vec4<f32>(normalize(vec3<f32>(xy, z)), f32(x & MASK_8BIT) / 255.0);
+}